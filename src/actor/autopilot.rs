@@ -0,0 +1,213 @@
+use std::collections::{HashMap, VecDeque};
+
+use bevy::prelude::*;
+
+use crate::actor::ghost::{ActiveGhost, Frightened, Ghost, GhostMovement};
+use crate::actor::movement::{Dir, NextDir, SetNextDir};
+use crate::actor::player::{Player, PlayerDeath};
+use crate::food::Food;
+use crate::from_env::FromEnv;
+use crate::grid::{GridLocation, MovingTo, SetGridLocation};
+use crate::layout::{ActorKind, Layout};
+
+pub struct AutopilotPlugin;
+
+impl Plugin for AutopilotPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Autopilot::from_env()).add_system(
+            drive
+                .label(SetNextDir)
+                .after(SetGridLocation)
+                .ambiguous_with(GhostMovement)
+                .before(PlayerDeath),
+        );
+    }
+}
+
+/// A bot that steers Pac-Man instead of the keyboard
+#[derive(Resource, Default, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Autopilot {
+    #[default]
+    Off,
+    /// Heads for the closest dot, and doesn't care about ghosts at all
+    Greedy,
+    /// Only goes where it can get before any ghost, and chases frightened ghosts
+    Cautious,
+}
+
+impl Autopilot {
+    pub fn next(self) -> Self {
+        match self {
+            Autopilot::Off => Autopilot::Greedy,
+            Autopilot::Greedy => Autopilot::Cautious,
+            Autopilot::Cautious => Autopilot::Off,
+        }
+    }
+
+    /// Which way Pac-Man should go next, or `None` to carry on
+    pub fn choose_dir(self, view: &PlayerView) -> Option<Dir> {
+        match self {
+            Autopilot::Off => None,
+            Autopilot::Greedy => greedy(view),
+            Autopilot::Cautious => cautious(view),
+        }
+    }
+}
+
+impl FromEnv for Autopilot {
+    fn with_env_overrides(self) -> Self {
+        get_autopilot().unwrap_or(self)
+    }
+}
+
+fn get_autopilot() -> Option<Autopilot> {
+    match std::env::var("AUTOPILOT").ok()?.to_lowercase().as_str() {
+        "off" => Some(Autopilot::Off),
+        "greedy" => Some(Autopilot::Greedy),
+        "cautious" => Some(Autopilot::Cautious),
+        _ => None,
+    }
+}
+
+/// Everything the autopilot can see when making a decision
+pub struct PlayerView<'a> {
+    pub location: GridLocation,
+    pub dir: Dir,
+    pub layout: &'a Layout,
+    /// Dots and energizers
+    pub food: &'a [GridLocation],
+    /// Ghosts that kill Pac-Man
+    pub ghosts: &'a [GridLocation],
+    /// Ghosts that Pac-Man can eat
+    pub frightened: &'a [GridLocation],
+}
+
+fn greedy(view: &PlayerView) -> Option<Dir> {
+    let paths = first_steps(view.layout, view.location, |_, _| true);
+    closest(&paths, view.food)
+}
+
+fn cautious(view: &PlayerView) -> Option<Dir> {
+    let danger = steps_from(view.layout, view.ghosts);
+
+    // Somewhere is safe if we'd get there with a tile to spare before the closest ghost
+    let safe = |loc: GridLocation, steps: u32| match danger.get(&loc) {
+        Some(ghost_steps) => steps + 1 < *ghost_steps,
+        None => true,
+    };
+    let paths = first_steps(view.layout, view.location, safe);
+
+    closest(&paths, view.frightened)
+        .or_else(|| closest(&paths, view.food))
+        .or_else(|| flee(view, &danger))
+}
+
+/// When nowhere is safe, get as far from the ghosts as possible
+fn flee(view: &PlayerView, danger: &HashMap<GridLocation, u32>) -> Option<Dir> {
+    view.layout
+        .neighbours(view.location)
+        .filter(|(_, loc)| view.layout.is_walkable_for(loc, ActorKind::PacMan))
+        .max_by_key(|(dir, loc)| {
+            let ghost_steps = danger.get(loc).copied().unwrap_or(u32::MAX);
+            // Prefer to keep going the same way when it's just as good
+            (ghost_steps, *dir == view.dir)
+        })
+        .map(|(dir, _)| dir)
+}
+
+/// The first step of the shortest path to the closest of the goals
+fn closest(paths: &HashMap<GridLocation, (u32, Dir)>, goals: &[GridLocation]) -> Option<Dir> {
+    goals
+        .iter()
+        .filter_map(|goal| paths.get(goal))
+        .min_by_key(|(steps, _)| *steps)
+        .map(|(_, dir)| *dir)
+}
+
+/// Breadth-first search for Pac-Man, recording how many steps it takes to get everywhere and
+/// which way to go first. Only goes through locations that are `open` after that many steps.
+fn first_steps(
+    layout: &Layout,
+    start: GridLocation,
+    open: impl Fn(GridLocation, u32) -> bool,
+) -> HashMap<GridLocation, (u32, Dir)> {
+    let mut paths = HashMap::new();
+    let mut queue = VecDeque::new();
+
+    for (dir, loc) in layout.neighbours(start) {
+        if layout.is_walkable_for(&loc, ActorKind::PacMan) && open(loc, 1) {
+            paths.insert(loc, (1, dir));
+            queue.push_back(loc);
+        }
+    }
+
+    while let Some(loc) = queue.pop_front() {
+        let (steps, first) = paths[&loc];
+
+        for (_, next) in layout.neighbours(loc) {
+            let walkable = layout.is_walkable_for(&next, ActorKind::PacMan);
+            if walkable && next != start && !paths.contains_key(&next) && open(next, steps + 1) {
+                paths.insert(next, (steps + 1, first));
+                queue.push_back(next);
+            }
+        }
+    }
+
+    paths
+}
+
+/// How many steps it takes the closest of the starts to get everywhere Pac-Man can go
+fn steps_from(layout: &Layout, starts: &[GridLocation]) -> HashMap<GridLocation, u32> {
+    let mut steps: HashMap<GridLocation, u32> = starts.iter().map(|loc| (*loc, 0)).collect();
+    let mut queue: VecDeque<GridLocation> = starts.iter().copied().collect();
+
+    while let Some(loc) = queue.pop_front() {
+        let next_steps = steps[&loc] + 1;
+
+        for (_, next) in layout.neighbours(loc) {
+            if layout.is_walkable_for(&next, ActorKind::PacMan) && !steps.contains_key(&next) {
+                steps.insert(next, next_steps);
+                queue.push_back(next);
+            }
+        }
+    }
+
+    steps
+}
+
+/// Pac-Man can only turn in the middle of a tile, so that's when the autopilot decides
+fn drive(
+    autopilot: Res<Autopilot>,
+    layout: Res<Layout>,
+    mut player: Query<(&GridLocation, &Dir, &mut NextDir), (With<Player>, Without<MovingTo>)>,
+    food: Query<&GridLocation, (With<Food>, Without<Ghost>)>,
+    ghosts: Query<&GridLocation, ActiveGhost>,
+    frightened: Query<&GridLocation, (With<Frightened>, With<Food>)>,
+) {
+    if *autopilot == Autopilot::Off {
+        return;
+    }
+
+    let mut food: Vec<GridLocation> = food.iter().copied().collect();
+    // Ties go the same way however the food was spawned, e.g. after loading a snapshot
+    food.sort_by_key(|loc| (loc.x, loc.y));
+    let ghosts: Vec<GridLocation> = ghosts.iter().copied().collect();
+    let frightened: Vec<GridLocation> = frightened.iter().copied().collect();
+
+    for (location, dir, mut next_dir) in &mut player {
+        let view = PlayerView {
+            location: *location,
+            dir: *dir,
+            layout: &layout,
+            food: &food,
+            ghosts: &ghosts,
+            frightened: &frightened,
+        };
+
+        if let Some(dir) = autopilot.choose_dir(&view) {
+            if **next_dir != Some(dir) {
+                **next_dir = Some(dir);
+            }
+        }
+    }
+}
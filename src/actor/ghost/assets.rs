@@ -1,39 +1,85 @@
 use bevy::{math::Rect, prelude::*};
 
 use crate::{
-    actor::movement::{moving_left, MovementBundle, BASE_SPEED},
+    actor::movement::{moving_left, MovementBundle},
+    from_env::FromEnv,
     grid::{GridBundle, GridLocation},
+    layout::ActorKind,
     level::{GridEntity, GRID},
+    players::GhostPlayer,
 };
 
-use super::{Ghost, GhostSpawner, PersonalityT, ScatterTarget, Target};
+use super::{
+    Chases, Ghost, GhostRoster, GhostSpawner, GhostSpec, Personality, Possessed, RosterIndex,
+    ScatterTarget, Target,
+};
 
 impl GhostSpawner {
-    pub fn spawn<P: PersonalityT>(
+    /// Spawns every ghost in the roster that starts on this spawn tile
+    pub fn spawn(&self, bldr: &mut ChildBuilder, spawn: char, location: GridLocation) {
+        let ghosts = self.roster.0.iter().enumerate();
+        for (index, ghost) in ghosts.filter(|(_, ghost)| ghost.spawn == spawn) {
+            self.spawn_ghost(bldr, index, ghost, location);
+        }
+    }
+
+    fn spawn_ghost(
         &self,
         bldr: &mut ChildBuilder,
-        personality: P,
+        index: usize,
+        ghost: &GhostSpec,
         location: GridLocation,
     ) {
-        bldr.spawn((
-            ScatterTarget(P::SCATTER),
+        let mut entity = bldr.spawn((
+            RosterIndex(index),
+            ScatterTarget(ghost.scatter),
             Ghost {
-                personality: P::VALUE,
+                personality: ghost.personality,
+                color: ghost.color(),
             },
-            personality,
+            ghost.personality.brain(),
             GridEntity {
-                name: Name::new(P::NAME),
-                texture_atlas: self.get_atlas(P::VALUE),
+                name: Name::new(ghost.name.clone()),
+                sprite: TextureAtlasSprite {
+                    color: ghost.tint,
+                    ..default()
+                },
+                texture_atlas: self.get_atlas(ghost.personality),
                 grid: GridBundle::new(GRID, location, default()),
                 ..default()
             },
-            MovementBundle {
-                speed: BASE_SPEED * 0.75,
-                ..default()
-            },
+            MovementBundle::default(),
             Target::default(),
-        ))
-        .insert(moving_left(location));
+            ActorKind::Ghost,
+        ));
+        entity.insert(moving_left(location));
+
+        if let Some(navigation) = ghost.navigation {
+            entity.insert(navigation);
+        }
+
+        if let Some(player) = ghost.chases {
+            entity.insert(Chases(player));
+        }
+
+        if self.is_possessed(index) {
+            entity.insert(Possessed);
+        }
+    }
+
+    /// Whether the ghost at this index in the roster is the first with the personality a human
+    /// plays as
+    fn is_possessed(&self, index: usize) -> bool {
+        let first = match *self.player {
+            Some(personality) => self
+                .roster
+                .0
+                .iter()
+                .position(|spec| spec.personality == personality),
+            None => return false,
+        };
+
+        first == Some(index)
     }
 }
 
@@ -46,41 +92,22 @@ impl FromWorld for GhostSpawner {
 
         let tile_size = Vec2::splat(16.0);
 
-        let blinky_atlas = TextureAtlas::from_grid(
-            sheet.clone(),
-            tile_size,
-            8,
-            1,
-            None,
-            Some(Vec2::new(456.0, 64.0)),
-        );
-
-        let pinky_atlas = TextureAtlas::from_grid(
-            sheet.clone(),
-            Vec2::splat(16.0),
-            8,
-            1,
-            None,
-            Some(Vec2::new(456.0, 80.0)),
-        );
-
-        let inky_atlas = TextureAtlas::from_grid(
-            sheet.clone(),
-            Vec2::splat(16.0),
-            8,
-            1,
-            None,
-            Some(Vec2::new(456.0, 96.0)),
-        );
-
-        let clyde_atlas = TextureAtlas::from_grid(
-            sheet.clone(),
-            Vec2::splat(16.0),
-            8,
-            1,
-            None,
-            Some(Vec2::new(456.0, 112.0)),
-        );
+        // Each personality has a row of sprites, in the same order
+        let bodies = Personality::ALL
+            .into_iter()
+            .enumerate()
+            .map(|(row, personality)| {
+                let atlas = TextureAtlas::from_grid(
+                    sheet.clone(),
+                    tile_size,
+                    8,
+                    1,
+                    None,
+                    Some(Vec2::new(456.0, 64.0 + row as f32 * 16.0)),
+                );
+                (personality, texture_atlases.add(atlas))
+            })
+            .collect();
 
         let mut frightened_atlas = TextureAtlas::new_empty(sheet.clone(), Vec2::new(680.0, 248.0));
         // Add same two sprites four times (the sprites are the same for each direction
@@ -107,10 +134,9 @@ impl FromWorld for GhostSpawner {
         }
 
         Self {
-            blinky: texture_atlases.add(blinky_atlas),
-            pinky: texture_atlases.add(pinky_atlas),
-            inky: texture_atlases.add(inky_atlas),
-            clyde: texture_atlases.add(clyde_atlas),
+            roster: GhostRoster::from_env(),
+            player: GhostPlayer::from_env(),
+            bodies,
             frightened: texture_atlases.add(frightened_atlas),
             respawning: texture_atlases.add(respawning_atlas),
         }
@@ -0,0 +1,226 @@
+use bevy::prelude::*;
+use rand::seq::IteratorRandom;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actor::mode::Mode,
+    actor::movement::Dir,
+    actor::player::{Player, PlayerNumber},
+    grid::GridLocation,
+    layout::{ActorKind, Layout},
+    random::GameRng,
+};
+
+use super::{Accuracy, ActiveGhost, Ghost, Personality, ScatterTarget, Target, Targeting};
+
+/// Decides where a ghost should go while it's chasing or scattering.
+///
+/// Brains only see a snapshot of the world, so they can be swapped out on any ghost at runtime
+/// by inserting a new [`Brain`] component. Any randomness has to come from `rng`, so that seeded
+/// games play out the same.
+pub trait GhostBrain: Send + Sync + 'static {
+    fn chase(&self, view: &GhostView, rng: &mut dyn RngCore) -> Steer;
+
+    fn scatter(&self, view: &GhostView, _rng: &mut dyn RngCore) -> Steer {
+        Steer::Target(view.scatter)
+    }
+
+    fn steer(&self, view: &GhostView, rng: &mut dyn RngCore) -> Steer {
+        match view.mode {
+            Mode::Chase => self.chase(view, rng),
+            Mode::Scatter => self.scatter(view, rng),
+        }
+    }
+}
+
+/// What a [`GhostBrain`] wants a ghost to do
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Steer {
+    /// Head towards a tile, picking the closest direction at each junction like the arcade
+    Target(GridLocation),
+    /// Turn this way at the next junction, if possible
+    Dir(Dir),
+}
+
+#[derive(Component)]
+pub struct Brain(pub Box<dyn GhostBrain>);
+
+impl Brain {
+    pub fn new(brain: impl GhostBrain) -> Self {
+        Self(Box::new(brain))
+    }
+}
+
+/// A direction chosen by a brain, which the ghost follows instead of its [`Target`]
+#[derive(Component, Deref, Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Heading(pub Dir);
+
+/// The player a ghost chases when there's more than one Pac-Man, instead of the nearest
+#[derive(Component, Copy, Clone, Debug, Deref)]
+pub struct Chases(pub PlayerNumber);
+
+#[derive(Copy, Clone, Debug)]
+pub struct ActorState {
+    pub location: GridLocation,
+    pub dir: Dir,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct GhostState {
+    pub personality: Personality,
+    pub location: GridLocation,
+}
+
+/// Everything a [`GhostBrain`] can see when making a decision
+pub struct GhostView<'a> {
+    pub location: GridLocation,
+    pub dir: Dir,
+    pub scatter: GridLocation,
+    /// The Pac-Man this ghost is after
+    pub player: ActorState,
+    /// Every ghost, including this one
+    pub ghosts: &'a [GhostState],
+    pub layout: &'a Layout,
+    pub mode: Mode,
+    pub accuracy: Accuracy,
+}
+
+impl GhostView<'_> {
+    /// Where the first ghost with a personality is, if there is one
+    pub fn ghost(&self, personality: Personality) -> Option<&GhostState> {
+        self.ghosts
+            .iter()
+            .find(|ghost| ghost.personality == personality)
+    }
+
+    /// The tile some distance in front of the player, including the arcade's bug when facing up
+    pub fn ahead_of_player(&self, tiles: isize) -> GridLocation {
+        self.accuracy
+            .ahead(self.player.location, self.player.dir, tiles)
+    }
+}
+
+/// Ignores the player and turns at random, in chase and scatter mode alike
+pub struct Wander;
+
+impl GhostBrain for Wander {
+    fn chase(&self, view: &GhostView, rng: &mut dyn RngCore) -> Steer {
+        let ahead = view.layout.shift(view.location, view.dir);
+
+        view.layout
+            .neighbours(ahead)
+            .filter(|(_, loc)| *loc != view.location)
+            .filter(|(_, loc)| view.layout.is_walkable_for(loc, ActorKind::Ghost))
+            .map(|(dir, _)| Steer::Dir(dir))
+            .choose(rng)
+            .unwrap_or(Steer::Dir(view.dir))
+    }
+
+    fn scatter(&self, view: &GhostView, rng: &mut dyn RngCore) -> Steer {
+        self.chase(view, rng)
+    }
+}
+
+/// The Pac-Man a ghost goes after: the one it [`Chases`], or else whichever is closest
+pub fn chased_player(
+    players: &[(PlayerNumber, ActorState)],
+    location: GridLocation,
+    chases: Option<&Chases>,
+) -> Option<ActorState> {
+    let assigned = chases.and_then(|chases| {
+        players
+            .iter()
+            .find(|(number, _)| *number == **chases)
+            .map(|(_, player)| *player)
+    });
+
+    assigned.or_else(|| {
+        players
+            .iter()
+            .map(|(_, player)| *player)
+            .min_by_key(|player| {
+                let (dx, dy) = (
+                    player.location.x - location.x,
+                    player.location.y - location.y,
+                );
+                dx * dx + dy * dy
+            })
+    })
+}
+
+type Thinker<'a> = (
+    Entity,
+    &'a Brain,
+    &'a GridLocation,
+    &'a Dir,
+    &'a ScatterTarget,
+    &'a mut Target,
+    Option<&'a Heading>,
+    Option<&'a Chases>,
+);
+
+#[allow(clippy::too_many_arguments)]
+pub fn think(
+    mut commands: Commands,
+    mode: Res<Mode>,
+    layout: Res<Layout>,
+    targeting: Res<Targeting>,
+    mut rng: ResMut<GameRng>,
+    mut thinkers: Query<Thinker, ActiveGhost>,
+    ghosts: Query<(&Ghost, &GridLocation)>,
+    players: Query<(&PlayerNumber, &GridLocation, &Dir), With<Player>>,
+) {
+    let players: Vec<(PlayerNumber, ActorState)> = players
+        .iter()
+        .map(|(number, location, dir)| {
+            let state = ActorState {
+                location: *location,
+                dir: *dir,
+            };
+            (*number, state)
+        })
+        .collect();
+
+    let ghosts: Vec<GhostState> = ghosts
+        .iter()
+        .map(|(ghost, location)| GhostState {
+            personality: ghost.personality,
+            location: *location,
+        })
+        .collect();
+
+    for (entity, brain, location, dir, scatter, mut target, heading, chases) in &mut thinkers {
+        let player = match chased_player(&players, *location, chases) {
+            Some(player) => player,
+            None => return,
+        };
+
+        let view = GhostView {
+            location: *location,
+            dir: *dir,
+            scatter: **scatter,
+            player,
+            ghosts: &ghosts,
+            layout: &layout,
+            mode: *mode,
+            accuracy: targeting.accuracy,
+        };
+
+        match brain.0.steer(&view, &mut **rng) {
+            Steer::Target(new_target) => {
+                if **target != new_target {
+                    **target = new_target;
+                }
+                if heading.is_some() {
+                    commands.entity(entity).remove::<Heading>();
+                }
+            }
+            Steer::Dir(new_heading) => {
+                if heading.map(|heading| **heading) != Some(new_heading) {
+                    commands.entity(entity).insert(Heading(new_heading));
+                }
+            }
+        }
+    }
+}
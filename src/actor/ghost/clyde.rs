@@ -1,43 +1,29 @@
 use bevy::prelude::*;
+use rand::RngCore;
 
-use crate::{actor::mode::Mode, actor::player::Player, grid::GridLocation};
+use crate::grid::GridLocation;
 
-use super::{ActiveGhost, Personality, PersonalityT, Target};
+use super::{GhostBrain, GhostView, Steer};
 
-#[derive(Component, Default)]
 pub struct Clyde;
 
-impl PersonalityT for Clyde {
-    const NAME: &'static str = "Clyde";
-    const COLOR: Color = Color::rgb(1.0, 0.72, 0.32);
-    const SCATTER: GridLocation = GridLocation { x: 0, y: 0 };
-    const VALUE: Personality = Personality::Clyde;
+impl Clyde {
+    pub const COLOR: Color = Color::rgb(1.0, 0.72, 0.32);
+    pub const SCATTER: GridLocation = GridLocation { x: 0, y: 0 };
 }
 
-pub fn chase(
-    mode: Res<Mode>,
-    mut query: Query<(&GridLocation, &mut Target), (ActiveGhost, With<Clyde>)>,
-    player: Query<&GridLocation, With<Player>>,
-) {
-    if *mode != Mode::Chase {
-        return;
-    }
-
-    for (clyde_loc, mut target) in &mut query {
-        let player_loc = player.get_single().unwrap();
-
-        let dist = player_loc
+impl GhostBrain for Clyde {
+    fn chase(&self, view: &GhostView, _rng: &mut dyn RngCore) -> Steer {
+        let dist = view
+            .player
+            .location
             .to_unscaled_vec2()
-            .distance_squared(clyde_loc.to_unscaled_vec2());
+            .distance_squared(view.location.to_unscaled_vec2());
 
-        let new_target = if dist > 8.0 * 8.0 {
-            *player_loc
+        if dist > 8.0 * 8.0 {
+            Steer::Target(view.player.location)
         } else {
-            Clyde::SCATTER
-        };
-
-        if **target != new_target {
-            **target = new_target;
+            Steer::Target(view.scatter)
         }
     }
 }
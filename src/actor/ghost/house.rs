@@ -1,10 +1,83 @@
 use bevy::prelude::*;
 
+use crate::{
+    actor::movement::Dir,
+    grid::GridLocation,
+    layout::{ActorKind, Layout, Tile},
+    level::{HEIGHT_TILES, WIDTH_TILES},
+};
+
+use super::pathfinding::{flood, pac_man_spawn};
+
+/// A ghost that has been regenerated and is on its way out of the house
 #[derive(Component, Default)]
 pub struct InHouse;
 
 /// Each ghost has a "dot counter" that increments when the player eat dots
 /// while they're in the house, until it reaches a limit and they leave.
 /// The counter is retained even when the ghost respawns.
+// Ghosts don't wait in the house at the start of a level yet, so nothing counts dots
+#[allow(dead_code)]
 #[derive(Component, Default)]
 pub struct DotCounter(u8);
+
+/// Where eaten ghosts go to regenerate
+#[derive(Copy, Clone, Debug)]
+pub struct House {
+    /// The tile just outside the door, where ghosts come back to life
+    pub entrance: GridLocation,
+    /// As far into the house as eyes can go straight through the door
+    pub floor: GridLocation,
+    /// Which way to go from the floor to get back out
+    pub exit_dir: Dir,
+}
+
+/// The house in the current layout, found again whenever the layout changes
+#[derive(Resource, Default, Deref)]
+pub struct MazeHouse(Option<House>);
+
+pub fn find_house(layout: Res<Layout>, mut house: ResMut<MazeHouse>) {
+    if layout.is_changed() {
+        *house = MazeHouse(House::find(&layout));
+    }
+}
+
+impl House {
+    /// The house behind the first door in the layout, if it has one.
+    /// The entrance is the side of the door Pac-Man can get to.
+    pub fn find(layout: &Layout) -> Option<House> {
+        let door = (0..HEIGHT_TILES as isize)
+            .flat_map(|y| (0..WIDTH_TILES as isize).map(move |x| GridLocation { x, y }))
+            .find(|loc| layout.get(loc) == Ok(Some(Tile::Door)))?;
+
+        let outside = flood(layout, ActorKind::PacMan, pac_man_spawn(layout));
+        let open = |loc: &GridLocation| {
+            layout.is_walkable_for(loc, ActorKind::HouseGhost)
+                && layout.get(loc) != Ok(Some(Tile::Door))
+        };
+
+        let entrance = layout
+            .neighbours(door)
+            .map(|(_, loc)| loc)
+            .find(|loc| outside.contains_key(loc))?;
+
+        let (in_dir, mut floor) = layout
+            .neighbours(door)
+            .find(|(_, loc)| open(loc) && !outside.contains_key(loc))?;
+
+        // Stop at the far wall, or if the house somehow leads back outside
+        for _ in 0..WIDTH_TILES.max(HEIGHT_TILES) {
+            let next = layout.shift(floor, in_dir);
+            if !open(&next) || outside.contains_key(&next) {
+                break;
+            }
+            floor = next;
+        }
+
+        Some(House {
+            entrance,
+            floor,
+            exit_dir: in_dir.opposite(),
+        })
+    }
+}
@@ -1,42 +1,30 @@
 use bevy::prelude::*;
+use rand::RngCore;
 
-use crate::{actor::movement::Dir, actor::player::Player, grid::GridLocation, level::WIDTH_TILES};
+use crate::{grid::GridLocation, level::WIDTH_TILES};
 
-use super::{blinky::Blinky, ActiveGhost, Mode, Personality, PersonalityT, Target};
+use super::{GhostBrain, GhostView, Personality, Steer};
 
-#[derive(Component, Default)]
 pub struct Inky;
 
-impl PersonalityT for Inky {
-    const NAME: &'static str = "Inky";
-    const COLOR: Color = Color::CYAN;
-    const SCATTER: GridLocation = GridLocation {
+impl Inky {
+    pub const COLOR: Color = Color::CYAN;
+    pub const SCATTER: GridLocation = GridLocation {
         x: WIDTH_TILES as isize - 1,
         y: 0,
     };
-    const VALUE: Personality = Personality::Inky;
 }
 
-pub fn chase(
-    mode: Res<Mode>,
-    mut query: Query<&mut Target, (ActiveGhost, With<Inky>)>,
-    player: Query<(&GridLocation, &Dir), With<Player>>,
-    blinky: Query<&GridLocation, With<Blinky>>,
-) {
-    if *mode != Mode::Chase {
-        return;
-    }
-
-    for mut target in &mut query {
-        let (player_loc, player_dir) = player.get_single().unwrap();
-        let blinky_loc = blinky.get_single().unwrap();
+impl GhostBrain for Inky {
+    fn chase(&self, view: &GhostView, _rng: &mut dyn RngCore) -> Steer {
+        let mut target = view.ahead_of_player(2);
 
-        let mut new_target = player_loc.shift_by(*player_dir, 2);
-        new_target.x += new_target.x - blinky_loc.x;
-        new_target.y += new_target.y - blinky_loc.y;
-
-        if **target != new_target {
-            **target = new_target;
+        // Without Blinky, just aim ahead of the player
+        if let Some(blinky) = view.ghost(Personality::Blinky) {
+            target.x += target.x - blinky.location.x;
+            target.y += target.y - blinky.location.y;
         }
+
+        Steer::Target(target)
     }
 }
@@ -1,27 +1,50 @@
 mod assets;
 mod blinky;
+mod brain;
 mod clyde;
 mod house;
 mod inky;
+mod pathfinding;
 mod pinky;
+mod possessed;
+mod prediction;
+mod roster;
+
+use std::collections::HashMap;
+use std::str::FromStr;
 
 use bevy::prelude::*;
 use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    actor::mode::{FrightenedMode, Mode, SetMode, TickMode},
-    actor::movement::{Dir, NextDir, StartLocation, BASE_SPEED},
+    actor::mode::{FrightenedMode, SetMode, TickMode},
+    actor::movement::{Dir, NextDir, StartLocation},
     food::{Eat, Food, WriteEatEvent},
-    grid::{GridLocation, SetGridLocation},
-    layout::Layout,
+    from_env::FromEnv,
+    grid::{Arrived, GridLocation, MovingTo, SetGridLocation, SetGridMoving},
+    layout::{ActorKind, Layout},
+    level::{CurrentLevel, GRID},
+    players::GhostPlayer,
+    random::GameRng,
 };
 
 pub use blinky::Blinky;
+pub use brain::{
+    chased_player, ActorState, Brain, Chases, GhostBrain, GhostView, Heading, Steer, Wander,
+};
 pub use clyde::Clyde;
+pub use house::InHouse;
 pub use inky::Inky;
+pub use pathfinding::{DistanceMaps, Navigation};
 pub use pinky::Pinky;
+pub use possessed::Possessed;
+pub use prediction::predict_path;
+pub use roster::{GhostRoster, GhostSpec, RosterIndex};
 
-use self::house::InHouse;
+use self::house::{find_house, MazeHouse};
+use self::pathfinding::clear_distance_maps;
+use self::possessed::steer_possessed;
 
 use super::movement::{SetDir, SetNextDir};
 
@@ -30,14 +53,23 @@ pub struct GhostPlugin;
 impl Plugin for GhostPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<GhostSpawner>()
+            .insert_resource(Targeting::from_env())
+            .init_resource::<DistanceMaps>()
+            .init_resource::<MazeHouse>()
+            .init_resource::<GameRng>()
             .add_system_set(
                 SystemSet::new()
                     .label(SetNextDir)
                     .label(GhostMovement)
                     .after(TickMode)
                     .before(SetGridLocation)
-                    .with_system(choose_next_dir)
-                    .with_system(frightened.ambiguous_with(choose_next_dir)),
+                    .with_system(clear_distance_maps)
+                    .with_system(find_house)
+                    .with_system(choose_next_dir.after(clear_distance_maps))
+                    .with_system(follow_heading.after(choose_next_dir))
+                    .with_system(frightened.ambiguous_with(choose_next_dir))
+                    // Has the last word, with the brain's choice as the fallback
+                    .with_system(steer_possessed.after(follow_heading).after(frightened)),
             )
             .add_system_set(
                 SystemSet::new()
@@ -45,11 +77,8 @@ impl Plugin for GhostPlugin {
                     .label(GhostMovement)
                     .after(SetMode)
                     .after(SetGridLocation)
-                    .with_system(blinky::chase)
-                    .with_system(pinky::chase.after(SetDir))
-                    .with_system(inky::chase.after(SetDir))
-                    .with_system(clyde::chase)
-                    .with_system(scatter),
+                    .after(SetDir)
+                    .with_system(brain::think),
             )
             .add_system(become_frightened.after(SetMode))
             .add_system(stop_frightened.after(SetMode))
@@ -58,7 +87,13 @@ impl Plugin for GhostPlugin {
                     .after(WriteEatEvent)
                     .after(SetTarget),
             )
-            .add_system(finish_respawning_eaten_ghost.after(SetTarget));
+            .add_system(
+                finish_respawning_eaten_ghost
+                    .after(SetTarget)
+                    // Turning around in the house has to override wherever we were going next
+                    .after(SetGridMoving),
+            )
+            .add_system(leave_house.after(SetTarget));
     }
 }
 
@@ -68,45 +103,53 @@ pub struct SetTarget;
 #[derive(SystemLabel)]
 pub struct GhostMovement;
 
+/// Spawns the ghosts in the roster, which is chosen with the `GHOSTS` environment variable
 #[derive(Resource)]
 pub struct GhostSpawner {
-    blinky: Handle<TextureAtlas>,
-    pinky: Handle<TextureAtlas>,
-    inky: Handle<TextureAtlas>,
-    clyde: Handle<TextureAtlas>,
+    pub roster: GhostRoster,
+    player: GhostPlayer,
+    bodies: HashMap<Personality, Handle<TextureAtlas>>,
     frightened: Handle<TextureAtlas>,
     respawning: Handle<TextureAtlas>,
 }
 
 impl GhostSpawner {
     fn get_atlas(&self, ghost: Personality) -> Handle<TextureAtlas> {
-        match ghost {
-            Personality::Blinky => &self.blinky,
-            Personality::Pinky => &self.pinky,
-            Personality::Inky => &self.inky,
-            Personality::Clyde => &self.clyde,
+        self.bodies[&ghost].clone()
+    }
+
+    /// What a ghost looks like while it's frightened or on its way home, or otherwise
+    pub(crate) fn look(
+        &self,
+        ghost: Personality,
+        frightened: bool,
+        respawning: bool,
+    ) -> Handle<TextureAtlas> {
+        if respawning {
+            self.respawning.clone()
+        } else if frightened {
+            self.frightened.clone()
+        } else {
+            self.get_atlas(ghost)
         }
-        .clone()
     }
 }
 
 #[derive(Component, Default)]
 pub struct Ghost {
     pub personality: Personality,
+    color: Color,
 }
 
 impl Ghost {
     pub fn color(&self) -> Color {
-        match self.personality {
-            Personality::Blinky => Blinky::COLOR,
-            Personality::Pinky => Pinky::COLOR,
-            Personality::Inky => Inky::COLOR,
-            Personality::Clyde => Clyde::COLOR,
-        }
+        self.color
     }
 }
 
-#[derive(Default, Copy, Clone)]
+/// How a ghost behaves and what it looks like, before any tint
+#[derive(Default, Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Personality {
     #[default]
     Blinky,
@@ -115,16 +158,119 @@ pub enum Personality {
     Clyde,
 }
 
-pub trait PersonalityT: Component + Default {
-    const NAME: &'static str;
-    const COLOR: Color;
-    const SCATTER: GridLocation;
-    const VALUE: Personality;
+impl Personality {
+    pub const ALL: [Personality; 4] = [
+        Personality::Blinky,
+        Personality::Pinky,
+        Personality::Inky,
+        Personality::Clyde,
+    ];
+
+    pub fn color(self) -> Color {
+        match self {
+            Personality::Blinky => Blinky::COLOR,
+            Personality::Pinky => Pinky::COLOR,
+            Personality::Inky => Inky::COLOR,
+            Personality::Clyde => Clyde::COLOR,
+        }
+    }
+
+    /// The brain ghosts with this personality start with
+    pub fn brain(self) -> Brain {
+        match self {
+            Personality::Blinky => Brain::new(Blinky),
+            Personality::Pinky => Brain::new(Pinky),
+            Personality::Inky => Brain::new(Inky),
+            Personality::Clyde => Brain::new(Clyde),
+        }
+    }
+}
+
+impl FromStr for Personality {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "blinky" => Ok(Personality::Blinky),
+            "pinky" => Ok(Personality::Pinky),
+            "inky" => Ok(Personality::Inky),
+            "clyde" => Ok(Personality::Clyde),
+            _ => Err(()),
+        }
+    }
 }
 
-#[derive(Component, Default, Deref, DerefMut, Copy, Clone)]
+#[derive(Component, Default, Deref, DerefMut, Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct Target(pub GridLocation);
 
+/// How ghosts measure the distance to their target when deciding which way to turn
+#[derive(Resource, Default)]
+pub struct Targeting {
+    /// Allow for shortcuts through portals.
+    /// The arcade ghosts don't, which is why they avoid tunnels when chasing.
+    pub wrapped_distance: bool,
+    pub accuracy: Accuracy,
+    /// For ghosts whose roster entry doesn't choose
+    pub navigation: Navigation,
+}
+
+/// Whether to copy the arcade's targeting bugs
+#[derive(Default, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Accuracy {
+    /// When Pac-Man faces up, a target "in front" of him is also the same number of tiles to the
+    /// left, because of an overflow in the arcade code
+    #[default]
+    Arcade,
+    Corrected,
+}
+
+impl Accuracy {
+    /// The tile some distance in front of an actor, including the arcade's bug when facing up
+    pub fn ahead(self, location: GridLocation, dir: Dir, tiles: isize) -> GridLocation {
+        let ahead = location.shift_by(dir, tiles);
+
+        match (dir, self) {
+            (Dir::Up, Accuracy::Arcade) => ahead.shift_by(Dir::Left, tiles),
+            _ => ahead,
+        }
+    }
+}
+
+impl FromEnv for Targeting {
+    fn with_env_overrides(mut self) -> Self {
+        if let Some(wrapped_distance) = get_ghost_wrapped_distance() {
+            self.wrapped_distance = wrapped_distance;
+        }
+        if let Some(accuracy) = get_ghost_targeting() {
+            self.accuracy = accuracy;
+        }
+        if let Some(navigation) = get_ghost_navigation() {
+            self.navigation = navigation;
+        }
+        self
+    }
+}
+
+fn get_ghost_wrapped_distance() -> Option<bool> {
+    std::env::var("GHOST_WRAPPED_DISTANCE").ok()?.parse().ok()
+}
+
+fn get_ghost_targeting() -> Option<Accuracy> {
+    match std::env::var("GHOST_TARGETING")
+        .ok()?
+        .to_lowercase()
+        .as_str()
+    {
+        "arcade" => Some(Accuracy::Arcade),
+        "corrected" => Some(Accuracy::Corrected),
+        _ => None,
+    }
+}
+
+fn get_ghost_navigation() -> Option<Navigation> {
+    std::env::var("GHOST_NAVIGATION").ok()?.parse().ok()
+}
+
 #[derive(Component, Default, Deref, DerefMut)]
 struct ScatterTarget(pub GridLocation);
 
@@ -147,44 +293,92 @@ pub type ActiveGhost = (
     Without<InHouse>,
 );
 
+/// Ghosts that have just moved to a new location, and aren't fleeing at random
+type AtDecisionPoint = (With<Arrived>, Without<Frightened>);
+
 const DIRECTIONS: [Dir; 4] = [Dir::Up, Dir::Left, Dir::Down, Dir::Right];
 
 // Ghosts decide on their next direction one grid location BEFORE
 fn choose_next_dir(
     layout: Res<Layout>,
+    targeting: Res<Targeting>,
+    mut distance_maps: ResMut<DistanceMaps>,
     mut query: Query<
-        (&Dir, &mut NextDir, &GridLocation, &Target),
-        (Changed<GridLocation>, Without<Frightened>),
+        (
+            &Dir,
+            &mut NextDir,
+            &GridLocation,
+            &Target,
+            &ActorKind,
+            Option<&Navigation>,
+        ),
+        AtDecisionPoint,
     >,
 ) {
-    for (dir, mut next_dir, loc, target) in &mut query {
-        let next_loc = loc.shift(*dir);
+    for (dir, mut next_dir, loc, target, kind, navigation) in &mut query {
+        let next_loc = layout.shift(*loc, *dir);
 
-        if layout.collides(&next_loc) {
+        if !layout.is_walkable_for(&next_loc, *kind) {
             continue;
         }
 
-        **next_dir = closest_dir_to_target(&layout, next_loc, *target, Some(*loc));
+        **next_dir = decide_next_dir(
+            &layout,
+            &targeting,
+            &mut distance_maps,
+            *kind,
+            navigation.copied(),
+            *loc,
+            next_loc,
+            *target,
+        );
     }
 }
 
-fn scatter(
-    mode: Res<Mode>,
-    mut query: Query<(Option<&Name>, &ScatterTarget, &mut Target), ActiveGhost>,
-) {
-    if *mode != Mode::Scatter {
-        return;
+/// Which way a ghost going from `loc` to `next_loc` will turn once it gets there
+#[allow(clippy::too_many_arguments)]
+fn decide_next_dir(
+    layout: &Layout,
+    targeting: &Targeting,
+    distance_maps: &mut DistanceMaps,
+    kind: ActorKind,
+    navigation: Option<Navigation>,
+    loc: GridLocation,
+    next_loc: GridLocation,
+    target: Target,
+) -> Option<Dir> {
+    let greedy = || closest_dir_to_target(layout, targeting, kind, next_loc, target, Some(loc));
+
+    let navigation = match kind {
+        // Going home means finding the door, wherever it is
+        ActorKind::HouseGhost => Navigation::Pathfinding,
+        _ => navigation.unwrap_or(targeting.navigation),
+    };
+
+    match navigation {
+        Navigation::Greedy => greedy(),
+        // Fall back to the arcade's way if the target is cut off from here
+        Navigation::Pathfinding => distance_maps
+            .next_dir(layout, kind, next_loc, *target, Some(loc))
+            .or_else(greedy),
     }
+}
 
-    for (name, scatter_target, mut target) in &mut query {
-        if **target != **scatter_target {
-            debug!(
-                "{} scattering: changing target from {:?} to {:?}",
-                name.unwrap_or(&Name::new("<Unknown>")),
-                **target,
-                **scatter_target
-            );
-            **target = **scatter_target;
+/// Ghosts whose brain picked a direction take it instead of heading for their target,
+/// as long as it doesn't mean turning around or walking into a wall
+fn follow_heading(
+    layout: Res<Layout>,
+    mut query: Query<(&Dir, &mut NextDir, &GridLocation, &Heading, &ActorKind), AtDecisionPoint>,
+) {
+    for (dir, mut next_dir, loc, heading, kind) in &mut query {
+        let next_loc = layout.shift(*loc, *dir);
+        let turn_loc = layout.shift(next_loc, **heading);
+
+        if layout.is_walkable_for(&next_loc, *kind)
+            && layout.is_walkable_for(&turn_loc, *kind)
+            && turn_loc != *loc
+        {
+            **next_dir = Some(**heading);
         }
     }
 }
@@ -207,7 +401,6 @@ fn become_frightened(
                     food: Food { points: 200 },
                     ..default()
                 },
-                BASE_SPEED * 0.5,
                 assets.frightened.clone(),
             ))
             .remove::<Target>();
@@ -217,10 +410,12 @@ fn become_frightened(
 fn stop_frightened(
     mut commands: Commands,
     mode: Res<FrightenedMode>,
+    level: Res<CurrentLevel>,
     assets: Res<GhostSpawner>,
     mut query: Query<(Entity, &Ghost), With<Frightened>>,
 ) {
-    if !mode.is_changed() || *mode == FrightenedMode::Enabled {
+    // A new or restarted level has just despawned these ghosts, and brought in fresh ones
+    if !mode.is_changed() || *mode == FrightenedMode::Enabled || level.is_changed() {
         return;
     }
 
@@ -228,25 +423,26 @@ fn stop_frightened(
         commands
             .entity(entity)
             .remove::<FrightenedBundle>()
-            .insert((
-                BASE_SPEED * 0.75,
-                assets.get_atlas(ghost.personality),
-                Target::default(),
-            ));
+            .insert((assets.get_atlas(ghost.personality), Target::default()));
     }
 }
 
 fn frightened(
     layout: Res<Layout>,
+    mut rng: ResMut<GameRng>,
     mut query: Query<
-        (&Dir, &mut NextDir, &GridLocation),
-        (With<Frightened>, Changed<GridLocation>),
+        (&RosterIndex, &Dir, &mut NextDir, &GridLocation, &ActorKind),
+        (With<Frightened>, With<Arrived>),
     >,
 ) {
-    for (dir, mut next_dir, loc) in &mut query {
-        let next_loc = loc.shift(*dir);
+    // Always in the same order, so each ghost gets the same random number every time
+    let mut query: Vec<_> = query.iter_mut().collect();
+    query.sort_by_key(|(index, ..)| **index);
+
+    for (_, dir, mut next_dir, loc, kind) in query {
+        let next_loc = layout.shift(*loc, *dir);
 
-        if layout.collides(&next_loc) {
+        if !layout.is_walkable_for(&next_loc, *kind) {
             continue;
         }
 
@@ -255,13 +451,13 @@ fn frightened(
         // If a wall blocks the chosen direction, the ghost then attempts the remaining directions
         // in this order: up, left, down, and right, until a passable direction is found.
         // https://www.gamedeveloper.com/design/the-pac-man-dossier#:~:text=The%20PRNG%20generates,direction%20is%20found.
-        let random_dir = *DIRECTIONS.choose(&mut rand::thread_rng()).unwrap();
+        let random_dir = *DIRECTIONS.choose(&mut **rng).unwrap();
 
         for candidate_dir in std::iter::once(random_dir).chain(DIRECTIONS) {
-            let candidate_loc = next_loc.shift(candidate_dir);
-            let collision = layout.collides(&candidate_loc);
+            let candidate_loc = layout.shift(next_loc, candidate_dir);
+            let walkable = layout.is_walkable_for(&candidate_loc, *kind);
 
-            if !collision && candidate_loc != *loc {
+            if walkable && candidate_loc != *loc {
                 **next_dir = Some(candidate_dir);
                 break;
             }
@@ -269,55 +465,99 @@ fn frightened(
     }
 }
 
+/// Eaten ghosts head for the floor of the house, through its door, or back to where they started
+/// in mazes without one
+#[allow(clippy::too_many_arguments)]
 fn start_respawning_eaten_ghost(
     mut commands: Commands,
     layout: Res<Layout>,
+    house: Res<MazeHouse>,
+    targeting: Res<Targeting>,
+    mut distance_maps: ResMut<DistanceMaps>,
     assets: Res<GhostSpawner>,
     mut eat_events: EventReader<Eat>,
     ghosts: Query<(&GridLocation, &StartLocation), With<Ghost>>,
 ) {
-    for Eat(eaten) in eat_events.iter() {
+    for Eat(eaten, _) in eat_events.iter() {
         if let Ok((location, start)) = ghosts.get(*eaten) {
-            let target = Target(**start);
+            let home = house.map_or(**start, |house| house.floor);
+            let kind = ActorKind::HouseGhost;
 
             commands
                 .entity(*eaten)
-                .insert((
-                    Respawning,
-                    target,
-                    // This speed is just a guess
-                    BASE_SPEED * 2.0,
-                    assets.respawning.clone(),
-                ))
-                .remove::<FrightenedBundle>();
-
-            if let Some(dir) = closest_dir_to_target(&layout, *location, target, None) {
+                .insert((Respawning, Target(home), kind, assets.respawning.clone()))
+                .remove::<(FrightenedBundle, Heading)>();
+
+            let dir = distance_maps
+                .next_dir(&layout, kind, *location, home, None)
+                .or_else(|| {
+                    closest_dir_to_target(&layout, &targeting, kind, *location, Target(home), None)
+                });
+
+            if let Some(dir) = dir {
                 commands.entity(*eaten).insert(dir);
             }
         }
     }
 }
 
+/// Eyes that reach home regenerate. Inside the house, they turn around to leave through the door.
 fn finish_respawning_eaten_ghost(
     mut commands: Commands,
+    house: Res<MazeHouse>,
     assets: Res<GhostSpawner>,
     mut respawning: Query<
-        (Entity, &Ghost, &StartLocation, &GridLocation),
+        (Entity, &Ghost, &Target, &GridLocation, &Transform),
         (With<Respawning>, Changed<GridLocation>),
     >,
 ) {
-    for (entity, ghost, start, location) in &mut respawning {
-        if **start == *location {
+    for (entity, ghost, target, location, transform) in &mut respawning {
+        if **target != *location {
+            continue;
+        }
+
+        commands
+            .entity(entity)
+            .insert(assets.get_atlas(ghost.personality))
+            .remove::<Respawning>();
+
+        match **house {
+            Some(house) if house.floor == *location => {
+                commands.entity(entity).insert((
+                    InHouse,
+                    Target(house.entrance),
+                    house.exit_dir,
+                    NextDir::default(),
+                    // Stop here rather than carrying on to wherever we were going next
+                    MovingTo(GRID.to_vec2(*location).extend(transform.translation.z)),
+                ));
+            }
+            _ => {
+                commands.entity(entity).insert(ActorKind::Ghost);
+            }
+        }
+    }
+}
+
+/// Regenerated ghosts are back in the game once they're out of the door
+fn leave_house(
+    mut commands: Commands,
+    leaving: Query<(Entity, &Target, &GridLocation), (With<InHouse>, Changed<GridLocation>)>,
+) {
+    for (entity, target, location) in &leaving {
+        if **target == *location {
             commands
                 .entity(entity)
-                .insert((BASE_SPEED * 0.75, assets.get_atlas(ghost.personality)))
-                .remove::<Respawning>();
+                .insert(ActorKind::Ghost)
+                .remove::<InHouse>();
         }
     }
 }
 
 fn closest_dir_to_target(
     layout: &Layout,
+    targeting: &Targeting,
+    kind: ActorKind,
     source: GridLocation,
     target: Target,
     original_loc: Option<GridLocation>,
@@ -325,15 +565,16 @@ fn closest_dir_to_target(
     let mut best_distance = f32::MAX;
     let mut best_dir = None;
 
-    for dir in DIRECTIONS {
-        let loc = source.shift(dir);
-        let collision = layout.collides(&loc);
+    for (dir, loc) in layout.neighbours(source) {
+        let walkable = layout.is_walkable_for(&loc, kind);
 
-        let distance = loc
-            .to_unscaled_vec2()
-            .distance_squared(target.to_unscaled_vec2());
+        let distance = if targeting.wrapped_distance {
+            layout.wrapped_distance(loc, *target)
+        } else {
+            loc.to_unscaled_vec2().distance(target.to_unscaled_vec2())
+        };
 
-        if !collision && original_loc != Some(loc) && distance < best_distance {
+        if walkable && original_loc != Some(loc) && distance < best_distance {
             best_dir = Some(dir);
             best_distance = distance;
         }
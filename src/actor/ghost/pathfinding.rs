@@ -0,0 +1,149 @@
+use std::collections::{HashMap, VecDeque};
+use std::str::FromStr;
+
+use bevy::prelude::*;
+
+use crate::{
+    actor::movement::Dir,
+    grid::GridLocation,
+    layout::{ActorKind, Layout, Tile},
+    level::{HEIGHT_TILES, WIDTH_TILES},
+};
+
+/// How a ghost picks which way to turn at a junction
+#[derive(Component, Default, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Navigation {
+    /// Turn towards whichever tile is closest to the target as the crow flies, like the arcade.
+    /// This gets stuck behind walls in some custom mazes.
+    #[default]
+    Greedy,
+    /// Follow the shortest path through the maze to the target, still never turning around
+    Pathfinding,
+}
+
+impl FromStr for Navigation {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "greedy" => Ok(Navigation::Greedy),
+            "pathfinding" => Ok(Navigation::Pathfinding),
+            _ => Err(()),
+        }
+    }
+}
+
+/// How many steps it takes to reach a target from every location that can reach it
+type DistanceMap = HashMap<GridLocation, u32>;
+
+/// Distance maps for each target ghosts have navigated towards in the current [`Layout`].
+///
+/// Targets mostly come from a handful of tiles (the player, the scatter corners, home), so each
+/// map is worked out once and reused until the layout changes.
+#[derive(Resource, Default)]
+pub struct DistanceMaps {
+    maps: HashMap<(ActorKind, GridLocation), DistanceMap>,
+    /// Everywhere reachable from Pac-Man's spawn, which targets are moved into
+    mazes: HashMap<ActorKind, DistanceMap>,
+}
+
+impl DistanceMaps {
+    /// The first direction on the shortest path from `source` to `target`, without going back to
+    /// `original_loc`. `None` if the target can't be reached that way.
+    pub fn next_dir(
+        &mut self,
+        layout: &Layout,
+        kind: ActorKind,
+        source: GridLocation,
+        target: GridLocation,
+        original_loc: Option<GridLocation>,
+    ) -> Option<Dir> {
+        let maze = self
+            .mazes
+            .entry(kind)
+            .or_insert_with(|| flood(layout, kind, pac_man_spawn(layout)));
+
+        let distances = self
+            .maps
+            .entry((kind, target))
+            .or_insert_with(|| flood(layout, kind, closest_in_maze(maze, target)));
+
+        let mut best_distance = u32::MAX;
+        let mut best_dir = None;
+
+        for (dir, loc) in layout.neighbours(source) {
+            if original_loc == Some(loc) || !layout.is_walkable_for(&loc, kind) {
+                continue;
+            }
+
+            if let Some(&distance) = distances.get(&loc) {
+                if distance < best_distance {
+                    best_dir = Some(dir);
+                    best_distance = distance;
+                }
+            }
+        }
+
+        best_dir
+    }
+
+    pub fn clear(&mut self) {
+        self.maps.clear();
+        self.mazes.clear();
+    }
+}
+
+/// Breadth-first search out from the start, through portals
+pub(super) fn flood(layout: &Layout, kind: ActorKind, start: Option<GridLocation>) -> DistanceMap {
+    let mut distances = DistanceMap::new();
+
+    let start = match start {
+        Some(start) if layout.is_walkable_for(&start, kind) => start,
+        _ => return distances,
+    };
+
+    let mut queue = VecDeque::from([start]);
+    distances.insert(start, 0);
+
+    while let Some(loc) = queue.pop_front() {
+        let distance = distances[&loc] + 1;
+
+        for (_, next) in layout.neighbours(loc) {
+            if layout.is_walkable_for(&next, kind) && !distances.contains_key(&next) {
+                distances.insert(next, distance);
+                queue.push_back(next);
+            }
+        }
+    }
+
+    distances
+}
+
+pub(super) fn pac_man_spawn(layout: &Layout) -> Option<GridLocation> {
+    (0..HEIGHT_TILES as isize)
+        .flat_map(|y| (0..WIDTH_TILES as isize).map(move |x| GridLocation { x, y }))
+        .find(|loc| layout.get(loc) == Ok(Some(Tile::PacMan)))
+}
+
+/// Targets are often inside walls or off the layout, so head for the closest place that isn't
+fn closest_in_maze(maze: &DistanceMap, target: GridLocation) -> Option<GridLocation> {
+    if maze.contains_key(&target) {
+        return Some(target);
+    }
+
+    let target_vec = target.to_unscaled_vec2();
+
+    // Break ties the same way every time, rather than in hash order
+    maze.keys().copied().min_by(|a, b| {
+        let distance = |loc: &GridLocation| loc.to_unscaled_vec2().distance_squared(target_vec);
+        distance(a)
+            .total_cmp(&distance(b))
+            .then((a.y, a.x).cmp(&(b.y, b.x)))
+    })
+}
+
+pub fn clear_distance_maps(layout: Res<Layout>, mut distance_maps: ResMut<DistanceMaps>) {
+    if layout.is_changed() {
+        distance_maps.clear();
+    }
+}
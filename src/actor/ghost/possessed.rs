@@ -0,0 +1,57 @@
+use bevy::prelude::*;
+
+use crate::{
+    actor::autopilot::Autopilot,
+    actor::movement::{Dir, NextDir},
+    actor::player::{DirInput, PlayerNumber},
+    grid::GridLocation,
+    layout::{ActorKind, Layout},
+    players::PacMen,
+};
+
+use super::DIRECTIONS;
+
+/// A ghost played by a human, who steers it with the controls of the player after the last
+/// Pac-Man, or the first player's too while the autopilot drives Pac-Man.
+///
+/// It still follows its brain's target whenever nothing is held, and still can't turn around.
+/// Once eaten it finds its own way home and out of the house again.
+#[derive(Component)]
+pub struct Possessed;
+
+/// Overrides whatever turn the ghost's brain, or being frightened, picked with the direction
+/// held. The brain keeps running for possessed ghosts on purpose: it's what steers when nothing
+/// is held, and what takes them home and back out of the house after being eaten.
+pub fn steer_possessed(
+    input: DirInput,
+    autopilot: Res<Autopilot>,
+    pac_men: Res<PacMen>,
+    layout: Res<Layout>,
+    mut query: Query<(&Dir, &mut NextDir, &GridLocation, &ActorKind), With<Possessed>>,
+) {
+    let player = PlayerNumber(**pac_men);
+    let pressed = |dir| {
+        input.pressed(player, dir)
+            || (*autopilot != Autopilot::Off && input.pressed(PlayerNumber(0), dir))
+    };
+
+    for (dir, mut next_dir, loc, kind) in &mut query {
+        // Going home or leaving the house
+        if *kind != ActorKind::Ghost {
+            continue;
+        }
+
+        // Like every ghost, the turn is for the tile it's heading to, not the one it's on
+        let next_loc = layout.shift(*loc, *dir);
+        let turn = DIRECTIONS.into_iter().find(|turn| {
+            let turn_loc = layout.shift(next_loc, *turn);
+            pressed(*turn) && layout.is_walkable_for(&turn_loc, *kind) && turn_loc != *loc
+        });
+
+        if let Some(turn) = turn {
+            if **next_dir != Some(turn) {
+                **next_dir = Some(turn);
+            }
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use crate::{
+    actor::movement::Dir,
+    grid::GridLocation,
+    layout::{ActorKind, Layout},
+};
+
+use super::{decide_next_dir, DistanceMaps, Navigation, Target, Targeting};
+
+/// The tiles a ghost will move through next, making the same decisions as it would in the game,
+/// as long as its target doesn't change and it isn't frightened
+#[allow(clippy::too_many_arguments)]
+pub fn predict_path(
+    layout: &Layout,
+    targeting: &Targeting,
+    distance_maps: &mut DistanceMaps,
+    kind: ActorKind,
+    navigation: Option<Navigation>,
+    location: GridLocation,
+    mut dir: Dir,
+    mut next_dir: Option<Dir>,
+    target: Target,
+    tiles: usize,
+) -> Vec<GridLocation> {
+    let mut path = Vec::with_capacity(tiles);
+    let mut loc = location;
+
+    for _ in 0..tiles {
+        let next_loc = layout.shift(loc, dir);
+        if !layout.is_walkable_for(&next_loc, kind) {
+            break;
+        }
+        loc = next_loc;
+        path.push(loc);
+
+        // On reaching a tile, ghosts take the turn they decided on one tile before...
+        if let Some(next) = next_dir {
+            if layout.is_walkable_for(&layout.shift(loc, next), kind) {
+                dir = next;
+            }
+        }
+
+        // ...then decide which way to turn at the one after it
+        let ahead = layout.shift(loc, dir);
+        if layout.is_walkable_for(&ahead, kind) {
+            next_dir = decide_next_dir(
+                layout,
+                targeting,
+                distance_maps,
+                kind,
+                navigation,
+                loc,
+                ahead,
+                target,
+            );
+        }
+    }
+
+    path
+}
@@ -0,0 +1,264 @@
+use std::fmt;
+use std::str::FromStr;
+
+use bevy::prelude::*;
+
+use crate::{
+    actor::player::PlayerNumber, from_env::FromEnv, grid::GridLocation, maze::parse_location,
+};
+
+use super::{Blinky, Clyde, Inky, Navigation, Personality, Pinky};
+
+/// A ghost to spawn at the start of each level
+#[derive(Clone, Debug)]
+pub struct GhostSpec {
+    pub name: String,
+    pub personality: Personality,
+    /// Multiplied with the personality's sprite, so white leaves it as drawn
+    pub tint: Color,
+    pub scatter: GridLocation,
+    /// The layout tile this ghost starts on. Several ghosts can share a spawn tile.
+    pub spawn: char,
+    /// Overrides the `GHOST_NAVIGATION` environment variable for this ghost
+    pub navigation: Option<Navigation>,
+    /// The player to chase when there's more than one Pac-Man, rather than the nearest
+    pub chases: Option<PlayerNumber>,
+}
+
+impl GhostSpec {
+    pub fn new(
+        name: &str,
+        personality: Personality,
+        scatter: GridLocation,
+        spawn: char,
+    ) -> GhostSpec {
+        GhostSpec {
+            name: name.to_string(),
+            personality,
+            tint: Color::WHITE,
+            scatter,
+            spawn,
+            navigation: None,
+            chases: None,
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        let [r1, g1, b1, a1] = self.personality.color().as_rgba_f32();
+        let [r2, g2, b2, a2] = self.tint.as_rgba_f32();
+        Color::rgba(r1 * r2, g1 * g2, b1 * b2, a1 * a2)
+    }
+}
+
+/// Where a ghost is in the [`GhostRoster`] it was spawned from, which tells apart ghosts that
+/// start on the same tile
+#[derive(Component, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Deref)]
+pub struct RosterIndex(pub usize);
+
+/// Every ghost that plays in a game.
+///
+/// Rosters are stored as text files with one ghost per line:
+/// `name personality spawn-tile scatter-corner [tint] [navigation] [player]`,
+/// e.g. `Sue clyde C 0,0` or `Hunter blinky B 25,35 pathfinding p2`.
+/// The player, from `p1`, is the Pac-Man the ghost chases when there's more than one.
+/// Blank lines and lines starting with `#` are ignored.
+#[derive(Clone, Debug)]
+pub struct GhostRoster(pub Vec<GhostSpec>);
+
+impl GhostRoster {
+    pub fn classic() -> Self {
+        Self(vec![
+            GhostSpec::new("Blinky", Personality::Blinky, Blinky::SCATTER, 'B'),
+            GhostSpec::new("Pinky", Personality::Pinky, Pinky::SCATTER, 'P'),
+            GhostSpec::new("Inky", Personality::Inky, Inky::SCATTER, 'I'),
+            GhostSpec::new("Clyde", Personality::Clyde, Clyde::SCATTER, 'C'),
+        ])
+    }
+
+    /// Sue takes Clyde's place, and behaves just like him
+    pub fn ms_pac_man() -> Self {
+        let mut roster = Self::classic();
+        roster.0[3].name = "Sue".to_string();
+        roster
+    }
+
+    pub fn spawning_at(&self, tile: char) -> impl Iterator<Item = &GhostSpec> {
+        self.0.iter().filter(move |ghost| ghost.spawn == tile)
+    }
+}
+
+impl Default for GhostRoster {
+    fn default() -> Self {
+        Self::classic()
+    }
+}
+
+impl FromStr for GhostRoster {
+    type Err = ParseRosterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lines = s
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+        let mut ghosts = vec![];
+
+        for line in lines {
+            let invalid = || ParseRosterError(line.to_string());
+            let fields: Vec<&str> = line.split_whitespace().collect();
+
+            let (name, personality, spawn, scatter, options) = match fields[..] {
+                [name, personality, spawn, scatter, ref options @ ..] if options.len() <= 3 => {
+                    (name, personality, spawn, scatter, options)
+                }
+                _ => return Err(invalid()),
+            };
+
+            let mut spawn_chars = spawn.chars();
+            let spawn = match (spawn_chars.next(), spawn_chars.next()) {
+                (Some(spawn), None) if spawn.is_ascii_uppercase() => spawn,
+                _ => return Err(invalid()),
+            };
+
+            let mut tint = Color::WHITE;
+            let mut navigation = None;
+            let mut chases = None;
+
+            for option in options {
+                if let Ok(option) = option.parse() {
+                    navigation = Some(option);
+                } else if let Some(player) = parse_player(option) {
+                    chases = Some(player);
+                } else {
+                    tint = Color::hex(option).map_err(|_| invalid())?;
+                }
+            }
+
+            ghosts.push(GhostSpec {
+                name: name.to_string(),
+                personality: personality.parse().map_err(|_| invalid())?,
+                tint,
+                scatter: parse_location(scatter).ok_or_else(invalid)?,
+                spawn,
+                navigation,
+                chases,
+            });
+        }
+
+        Ok(Self(ghosts))
+    }
+}
+
+/// A player from `p1`
+fn parse_player(s: &str) -> Option<PlayerNumber> {
+    let number: usize = s.strip_prefix('p')?.parse().ok()?;
+    number.checked_sub(1).map(PlayerNumber)
+}
+
+#[derive(Debug)]
+pub struct ParseRosterError(String);
+
+impl fmt::Display for ParseRosterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expected 'name personality spawn-tile x,y [tint] [navigation] [player]' but found {:?}",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ParseRosterError {}
+
+impl FromEnv for GhostRoster {
+    fn with_env_overrides(self) -> Self {
+        match get_ghosts().as_deref() {
+            None => self,
+            Some("classic") => Self::classic(),
+            Some("ms-pac-man") => Self::ms_pac_man(),
+            Some(path) => {
+                let roster = match std::fs::read_to_string(path) {
+                    Ok(roster) => roster,
+                    Err(err) => {
+                        error!(
+                            "Can't read ghost roster {path}, playing the classic ghosts instead: {err}"
+                        );
+                        return Self::classic();
+                    }
+                };
+                match roster.parse() {
+                    Ok(roster) => roster,
+                    Err(err) => {
+                        error!(
+                            "Invalid ghost roster {path}, playing the classic ghosts instead: {err}"
+                        );
+                        Self::classic()
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Either `classic`, `ms-pac-man` or the path to a roster file
+fn get_ghosts() -> Option<String> {
+    std::env::var("GHOSTS").ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ghosts_in_order() {
+        let roster: GhostRoster = "
+            # The first ghost is always red
+            Blinky  blinky  B  25,35
+
+            Sue     clyde   C  0,0    ff8000
+        "
+        .parse()
+        .unwrap();
+
+        assert_eq!(roster.0.len(), 2);
+        let (blinky, sue) = (&roster.0[0], &roster.0[1]);
+        assert_eq!(blinky.name, "Blinky");
+        assert_eq!(blinky.personality, Personality::Blinky);
+        assert_eq!(blinky.spawn, 'B');
+        assert_eq!(blinky.scatter, GridLocation { x: 25, y: 35 });
+        assert_eq!(blinky.tint, Color::WHITE);
+
+        assert_eq!(sue.name, "Sue");
+        assert_eq!(sue.personality, Personality::Clyde);
+        assert_eq!(sue.spawn, 'C');
+        assert_eq!(sue.scatter, GridLocation { x: 0, y: 0 });
+        assert_eq!(sue.tint, Color::hex("ff8000").unwrap());
+    }
+
+    #[test]
+    fn rejects_bad_lines() {
+        for line in [
+            "Blinky blinky B",
+            "Blinky blinky B 25,35 ff0000 extra",
+            "Blinky shadow B 25,35",
+            "Blinky blinky b 25,35",
+            "Blinky blinky BB 25,35",
+            "Blinky blinky B 25",
+            "Blinky blinky B 25,35 red",
+        ] {
+            let err = line.parse::<GhostRoster>().unwrap_err();
+            assert_eq!(err.0, line);
+        }
+    }
+
+    #[test]
+    fn the_bundled_rosters_parse() {
+        for roster in [
+            include_str!("../../../assets/rosters/two.roster"),
+            include_str!("../../../assets/rosters/eight.roster"),
+        ] {
+            assert!(!roster.parse::<GhostRoster>().unwrap().0.is_empty());
+        }
+    }
+}
@@ -1,4 +1,6 @@
+pub mod autopilot;
 pub mod ghost;
 pub mod mode;
 pub mod movement;
 pub mod player;
+pub mod speed;
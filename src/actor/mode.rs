@@ -1,6 +1,9 @@
 use std::time::Duration;
 
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::level::{CurrentLevel, StartLevel};
 
 pub struct ModePlugin;
 
@@ -10,7 +13,18 @@ impl Plugin for ModePlugin {
             .init_resource::<FrightenedMode>()
             .init_resource::<ModeTimer>()
             .init_resource::<FrightenedTimer>()
-            .add_system(tick_mode.label(TickMode).label(SetMode))
+            .add_system(
+                reset_mode_on_new_level
+                    .label(TickMode)
+                    .label(SetMode)
+                    .after(StartLevel),
+            )
+            .add_system(
+                tick_mode
+                    .label(TickMode)
+                    .label(SetMode)
+                    .after(reset_mode_on_new_level),
+            )
             .add_system(
                 tick_frightened
                     .label(TickMode)
@@ -27,7 +41,8 @@ pub struct TickMode;
 #[derive(SystemLabel)]
 pub struct SetMode;
 
-#[derive(Resource, Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Resource, Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Mode {
     Scatter,
     Chase,
@@ -39,16 +54,18 @@ impl Default for Mode {
     }
 }
 
-#[derive(Resource, Default, PartialEq, Eq)]
+#[derive(Resource, Default, Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum FrightenedMode {
     #[default]
     Disabled,
     Enabled,
 }
 
-#[derive(Resource)]
-struct ModeTimer {
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct ModeTimer {
     index: usize,
+    #[serde(with = "crate::snapshot::saved_timer")]
     timer: Timer,
 }
 
@@ -64,8 +81,8 @@ impl Default for ModeTimer {
     }
 }
 
-#[derive(Resource, Deref, DerefMut)]
-struct FrightenedTimer(Timer);
+#[derive(Resource, Clone, Debug, Deref, DerefMut, Serialize, Deserialize)]
+pub(crate) struct FrightenedTimer(#[serde(with = "crate::snapshot::saved_timer")] Timer);
 
 impl Default for FrightenedTimer {
     fn default() -> Self {
@@ -75,13 +92,36 @@ impl Default for FrightenedTimer {
     }
 }
 
+fn reset_mode_on_new_level(
+    level: Res<CurrentLevel>,
+    mut mode: ResMut<Mode>,
+    mut mode_timer: ResMut<ModeTimer>,
+    mut frightened_mode: ResMut<FrightenedMode>,
+    mut frightened_timer: ResMut<FrightenedTimer>,
+) {
+    if !level.is_changed() {
+        return;
+    }
+
+    *mode = Mode::default();
+    *mode_timer = ModeTimer::default();
+    *frightened_timer = FrightenedTimer::default();
+    if *frightened_mode == FrightenedMode::Enabled {
+        *frightened_mode = FrightenedMode::Disabled;
+    }
+}
+
 fn tick_mode(time: Res<Time>, mut mode_timer: ResMut<ModeTimer>, mut mode: ResMut<Mode>) {
     if !mode_timer.timer.tick(time.delta()).finished() {
         return;
     }
 
+    // The last mode lasts forever, even though being frightened unpauses the timer
+    let (new_mode, new_duration) = match MODE_TABLE.get(mode_timer.index + 1) {
+        Some(entry) => *entry,
+        None => return,
+    };
     mode_timer.index += 1;
-    let (new_mode, new_duration) = MODE_TABLE[mode_timer.index];
     info!("{:?} mode for {:?} seconds", new_mode, new_duration);
     *mode = new_mode;
 
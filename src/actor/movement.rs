@@ -1,10 +1,11 @@
 use crate::grid::{
     Grid, GridLocation, Layer, MovingTo, SetGridLocation, SetGridMoving, SetTransform, Speed,
 };
-use crate::layout::Layout;
-use crate::level::{GRID, GRID_SIZE, WIDTH};
+use crate::layout::{ActorKind, Layout};
+use crate::level::GRID;
 use bevy::prelude::*;
 use bevy_inspector_egui::Inspectable;
+use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
 pub const BASE_SPEED: Speed = Speed(88.0);
@@ -24,7 +25,7 @@ impl Plugin for MovementPlugin {
             .add_system(move_dir.label(SetGridMoving).after(SetDir))
             .add_system(move_to.label(SetTransform).after(SetSpeed))
             .add_system(
-                wrap_left_right
+                go_through_portals
                     .label(SetTransform)
                     .before(SetGridLocation)
                     .after(move_to),
@@ -47,7 +48,8 @@ pub struct MovementBundle {
     pub speed: Speed,
 }
 
-#[derive(Component, Copy, Clone, Eq, PartialEq, Debug, Inspectable)]
+#[derive(Component, Copy, Clone, Eq, PartialEq, Debug, Inspectable, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Dir {
     Left,
     Right,
@@ -55,6 +57,17 @@ pub enum Dir {
     Up,
 }
 
+impl Dir {
+    pub fn opposite(self) -> Dir {
+        match self {
+            Dir::Left => Dir::Right,
+            Dir::Right => Dir::Left,
+            Dir::Down => Dir::Up,
+            Dir::Up => Dir::Down,
+        }
+    }
+}
+
 #[derive(Debug, Component, Default, Deref, DerefMut, Inspectable)]
 pub struct NextDir(Option<Dir>);
 
@@ -87,11 +100,11 @@ pub fn moving_left(location: GridLocation) -> impl Bundle {
 fn move_dir(
     mut commands: Commands,
     layout: Res<Layout>,
-    query: Query<(Entity, &GridLocation, &Grid, &Layer, &Dir), Without<MovingTo>>,
+    query: Query<(Entity, &GridLocation, &Grid, &Layer, &Dir, &ActorKind), Without<MovingTo>>,
 ) {
-    for (entity, location, grid, layer, dir) in &query {
+    for (entity, location, grid, layer, dir, kind) in &query {
         let new_loc = location.shift(*dir);
-        if !layout.collides(&new_loc) {
+        if layout.is_walkable_for(&new_loc, *kind) {
             commands
                 .entity(entity)
                 .insert(MovingTo(grid.to_vec2(new_loc).extend(layer.0)));
@@ -101,11 +114,11 @@ fn move_dir(
 
 fn change_to_next_dir(
     layout: Res<Layout>,
-    mut query: Query<(&GridLocation, &NextDir, &mut Dir), Without<MovingTo>>,
+    mut query: Query<(&GridLocation, &NextDir, &ActorKind, &mut Dir), Without<MovingTo>>,
 ) {
-    for (location, next, mut dir) in &mut query {
+    for (location, next, kind, mut dir) in &mut query {
         if let Some(next) = next.0 {
-            if !layout.collides(&location.shift(next)) && *dir != next {
+            if layout.is_walkable_for(&location.shift(next), *kind) && *dir != next {
                 *dir = next;
             }
         }
@@ -159,17 +172,20 @@ fn move_to(
     }
 }
 
-fn wrap_left_right(mut query: Query<(&mut Transform, Option<&mut MovingTo>)>) {
-    const MARGIN: f32 = 2.0 * GRID_SIZE;
+fn go_through_portals(
+    layout: Res<Layout>,
+    mut query: Query<(&Grid, &Dir, &mut Transform, Option<&mut MovingTo>)>,
+) {
+    for (grid, dir, mut transform, moving_to) in &mut query {
+        let location = grid.to_grid_location(transform.translation.truncate());
 
-    for (mut transform, moving_to) in &mut query {
-        let new_x = (transform.translation.x + MARGIN).rem_euclid(WIDTH + MARGIN * 2.0) - MARGIN;
-        // Check so we don't trigger change detection
-        if (transform.translation.x - new_x).abs() > MARGIN {
+        if let Some(exit) = layout.through_portal(location, *dir) {
+            // Shift rather than teleport to the exit, so we keep moving smoothly
+            let offset = (grid.to_vec2(exit) - grid.to_vec2(location)).extend(0.0);
             if let Some(mut moving_to) = moving_to {
-                moving_to.x += new_x - transform.translation.x;
+                **moving_to += offset;
             }
-            transform.translation.x = new_x;
+            transform.translation += offset;
         }
     }
 }
@@ -1,18 +1,25 @@
+use std::marker::PhantomData;
+
+use crate::actor::autopilot::Autopilot;
 use crate::actor::ghost::ActiveGhost;
-use crate::actor::mode::FrightenedMode;
-use crate::actor::movement::{Dir, NextDir, SetNextDir, BASE_SPEED};
-use crate::grid::{GridLocation, SetGridLocation, Speed};
+use crate::actor::movement::{Dir, NextDir, SetNextDir};
+use crate::controls::{Action, Controls};
+use crate::from_env::FromEnv;
+use crate::grid::{GridLocation, SetGridLocation};
+use crate::players::{GhostPlayer, PacMen};
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use super::ghost::GhostMovement;
-use super::mode::SetMode;
-use super::movement::SetSpeed;
 
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<Lives>()
+            .insert_resource(StickDeadzone::from_env())
+            .insert_resource(TurnBuffer::from_env())
             .add_event::<PlayerDied>()
             .add_system(
                 player_controls
@@ -25,8 +32,7 @@ impl Plugin for PlayerPlugin {
                     .label(PlayerDeath)
                     .after(SetGridLocation),
             )
-            .add_system(lose_life_when_dying.label(UpdateLives).after(PlayerDeath))
-            .add_system(set_speed.label(SetSpeed).after(SetMode));
+            .add_system(lose_life_when_dying.label(UpdateLives).after(PlayerDeath));
     }
 }
 
@@ -39,7 +45,11 @@ pub struct UpdateLives;
 #[derive(Component)]
 pub struct Player;
 
-#[derive(Resource, Deref, DerefMut)]
+/// Which of the [`PacMen`] on the board this is, starting from 0
+#[derive(Component, Copy, Clone, Eq, PartialEq, Debug, Deref)]
+pub struct PlayerNumber(pub usize);
+
+#[derive(Resource, Clone, Debug, Deref, DerefMut, Serialize, Deserialize)]
 pub struct Lives(usize);
 
 impl Default for Lives {
@@ -48,60 +58,230 @@ impl Default for Lives {
     }
 }
 
-pub struct PlayerDied;
+/// How far the analog stick has to be pushed before Pac-Man turns, from 0 to 1
+#[derive(Resource, Copy, Clone, Debug, Deref)]
+pub struct StickDeadzone(pub f32);
+
+impl Default for StickDeadzone {
+    fn default() -> Self {
+        Self(0.4)
+    }
+}
+
+impl FromEnv for StickDeadzone {
+    fn with_env_overrides(self) -> Self {
+        get_stick_deadzone().map(Self).unwrap_or(self)
+    }
+}
+
+fn get_stick_deadzone() -> Option<f32> {
+    std::env::var("STICK_DEADZONE").ok()?.parse().ok()
+}
+
+/// How many tiles Pac-Man remembers a turn for after its key is let go, so taps just before
+/// a junction aren't lost
+#[derive(Resource, Copy, Clone, Debug, Deref)]
+pub struct TurnBuffer(pub u32);
+
+impl Default for TurnBuffer {
+    fn default() -> Self {
+        Self(2)
+    }
+}
+
+impl FromEnv for TurnBuffer {
+    fn with_env_overrides(self) -> Self {
+        get_turn_buffer().map(Self).unwrap_or(self)
+    }
+}
+
+fn get_turn_buffer() -> Option<u32> {
+    std::env::var("TURN_BUFFER").ok()?.parse().ok()
+}
+
+/// The directions the player is holding, so the most recently pressed one wins
+#[derive(Component, Default, Clone, Debug, Serialize, Deserialize)]
+pub struct InputBuffer {
+    /// Oldest first
+    held: Vec<Dir>,
+    /// The last turn asked for, which is forgotten after a few tiles once nothing is held
+    buffered: Option<Dir>,
+    tiles_left: u32,
+    location: Option<GridLocation>,
+}
+
+impl InputBuffer {
+    /// Catches up with what's held now, and returns the turn to take, if there is one
+    fn update(
+        &mut self,
+        pressed: impl Fn(Dir) -> bool,
+        location: GridLocation,
+        buffer: TurnBuffer,
+    ) -> Option<Dir> {
+        self.held.retain(|dir| pressed(*dir));
+        for dir in [Dir::Left, Dir::Right, Dir::Down, Dir::Up] {
+            if pressed(dir) && !self.held.contains(&dir) {
+                self.held.push(dir);
+            }
+        }
+
+        let moved = self.location.replace(location) != Some(location);
+
+        if let Some(dir) = self.held.last() {
+            self.buffered = Some(*dir);
+            self.tiles_left = *buffer;
+        } else if moved && self.buffered.is_some() {
+            self.tiles_left = self.tiles_left.saturating_sub(1);
+            if self.tiles_left == 0 {
+                self.buffered = None;
+            }
+        }
+
+        self.buffered
+    }
+}
+
+/// The direction each player is holding, when that comes from somewhere other than this machine's
+/// keyboard and gamepads, like an agent in the gym or the other end of a network game. While
+/// it's there, it's all that steers.
+#[derive(Resource, Default, Clone, Debug)]
+pub struct SyncedInput(pub Vec<Option<Dir>>);
+
+/// Everything the player can steer with
+#[derive(SystemParam)]
+pub struct DirInput<'w, 's> {
+    keyboard: Res<'w, Input<KeyCode>>,
+    controls: Res<'w, Controls>,
+    gamepads: Res<'w, Gamepads>,
+    buttons: Res<'w, Input<GamepadButton>>,
+    axes: Res<'w, Axis<GamepadAxis>>,
+    deadzone: Res<'w, StickDeadzone>,
+    pac_men: Res<'w, PacMen>,
+    ghost_player: Res<'w, GhostPlayer>,
+    synced: Option<Res<'w, SyncedInput>>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+impl DirInput<'_, '_> {
+    /// Whether any of a player's keys or gamepads are held in a direction.
+    /// With more than one player, each gamepad steers the player with its number.
+    pub fn pressed(&self, player: PlayerNumber, dir: Dir) -> bool {
+        if let Some(synced) = &self.synced {
+            return synced.0.get(*player).copied().flatten() == Some(dir);
+        }
+
+        self.pressed_here(player, dir)
+    }
+
+    /// Whether any of a player's keys or gamepads on this machine are held in a direction, even
+    /// while [`SyncedInput`] is steering
+    pub fn pressed_here(&self, player: PlayerNumber, dir: Dir) -> bool {
+        let players = **self.pac_men + usize::from(self.ghost_player.is_some());
+
+        let keys = match Action::steer(*player, dir) {
+            Some(action) => self.controls.pressed(&self.keyboard, action),
+            None => false,
+        };
+
+        keys || self
+            .gamepads
+            .iter()
+            .enumerate()
+            .filter(|(number, _)| players == 1 || *number == *player)
+            .any(|(_, gamepad)| {
+                gamepad_dir(gamepad, &self.buttons, &self.axes, *self.deadzone) == Some(dir)
+            })
+    }
+}
+
+pub struct PlayerDied {
+    /// The ghost that caught Pac-Man, if one did
+    pub ghost: Option<Entity>,
+}
 
 fn player_controls(
-    keyboard_input: Res<Input<KeyCode>>,
-    mut query: Query<&mut NextDir, With<Player>>,
+    input: DirInput,
+    buffer: Res<TurnBuffer>,
+    autopilot: Res<Autopilot>,
+    mut query: Query<(&mut NextDir, &mut InputBuffer, &GridLocation, &PlayerNumber), With<Player>>,
 ) {
-    let mut player = query.single_mut();
-
-    if keyboard_input.pressed(KeyCode::Left) {
-        **player = Some(Dir::Left);
+    if *autopilot != Autopilot::Off {
+        return;
     }
-    if keyboard_input.pressed(KeyCode::Right) {
-        **player = Some(Dir::Right);
+
+    for (mut next_dir, mut input_buffer, location, player) in &mut query {
+        let buffered = input_buffer.buffered;
+
+        match input_buffer.update(|dir| input.pressed(*player, dir), *location, *buffer) {
+            Some(turn) if **next_dir != Some(turn) => **next_dir = Some(turn),
+            Some(_) => {}
+            // The buffered turn has run out
+            None if buffered.is_some() => **next_dir = None,
+            None => {}
+        }
     }
-    if keyboard_input.pressed(KeyCode::Down) {
-        **player = Some(Dir::Down);
+}
+
+/// The d-pad if it's pressed, otherwise whichever way the left stick is pushed furthest,
+/// so diagonals still pick one of the four directions
+fn gamepad_dir(
+    gamepad: Gamepad,
+    buttons: &Input<GamepadButton>,
+    axes: &Axis<GamepadAxis>,
+    deadzone: StickDeadzone,
+) -> Option<Dir> {
+    let dpad = [
+        (GamepadButtonType::DPadLeft, Dir::Left),
+        (GamepadButtonType::DPadRight, Dir::Right),
+        (GamepadButtonType::DPadDown, Dir::Down),
+        (GamepadButtonType::DPadUp, Dir::Up),
+    ];
+    let pressed = dpad
+        .into_iter()
+        .find(|(button, _)| buttons.pressed(GamepadButton::new(gamepad, *button)))
+        .map(|(_, dir)| dir);
+    if pressed.is_some() {
+        return pressed;
     }
-    if keyboard_input.pressed(KeyCode::Up) {
-        **player = Some(Dir::Up);
+
+    let axis = |axis_type| {
+        axes.get(GamepadAxis::new(gamepad, axis_type))
+            .unwrap_or_default()
+    };
+    let stick = Vec2::new(
+        axis(GamepadAxisType::LeftStickX),
+        axis(GamepadAxisType::LeftStickY),
+    );
+
+    if stick.length() < *deadzone {
+        None
+    } else if stick.x.abs() > stick.y.abs() {
+        Some(if stick.x < 0.0 { Dir::Left } else { Dir::Right })
+    } else {
+        Some(if stick.y < 0.0 { Dir::Down } else { Dir::Up })
     }
 }
 
 fn die_when_touching_ghost(
     player: Query<&GridLocation, With<Player>>,
-    ghosts: Query<&GridLocation, ActiveGhost>,
+    ghosts: Query<(Entity, &GridLocation), ActiveGhost>,
     mut death_events: EventWriter<PlayerDied>,
 ) {
-    let player = player.single();
-
-    for ghost in &ghosts {
-        if player == ghost {
-            death_events.send(PlayerDied);
-            return;
+    for player in &player {
+        for (ghost, location) in &ghosts {
+            if player == location {
+                death_events.send(PlayerDied { ghost: Some(ghost) });
+                return;
+            }
         }
     }
 }
 
 fn lose_life_when_dying(mut deaths: EventReader<PlayerDied>, mut lives: ResMut<Lives>) {
     for _ in deaths.iter() {
-        // saturating_sub until we have game over behaviour
+        // The game's over at zero, but more deaths can come in before that's dealt with,
+        // e.g. from the debug die key in the same frame
         **lives = lives.saturating_sub(1);
     }
 }
-
-fn set_speed(mode: Res<FrightenedMode>, mut player_speed: Query<&mut Speed, With<Player>>) {
-    if !mode.is_changed() {
-        return;
-    }
-
-    let new_speed = if *mode == FrightenedMode::Enabled {
-        0.9
-    } else {
-        0.8
-    };
-
-    *player_speed.single_mut() = BASE_SPEED * new_speed
-}
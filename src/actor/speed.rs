@@ -0,0 +1,310 @@
+//! How fast everything moves, as a percentage of [`BASE_SPEED`], following the arcade's tables.
+//! https://www.gamedeveloper.com/design/the-pac-man-dossier#:~:text=Speed
+
+use bevy::prelude::*;
+
+use crate::actor::ghost::{Frightened, Ghost, InHouse, Personality, Respawning};
+use crate::actor::mode::{FrightenedMode, SetMode};
+use crate::actor::movement::{Dir, SetSpeed, BASE_SPEED};
+use crate::actor::player::Player;
+use crate::food::Food;
+use crate::grid::{GridLocation, Speed};
+use crate::layout::Layout;
+use crate::level::{CurrentLevel, WIDTH_TILES};
+
+pub struct SpeedPlugin;
+
+impl Plugin for SpeedPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(set_speed.label(SetSpeed).after(SetMode));
+    }
+}
+
+/// What an actor is up to, as far as its speed is concerned
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum SpeedState {
+    PacMan,
+    /// Pac-Man while the ghosts are frightened
+    PacManFrightened,
+    Ghost,
+    GhostFrightened,
+    /// Ghosts slow down in the tunnels, even when frightened
+    GhostInTunnel,
+    /// Blinky speeds up as the dots run out, with a second boost (2) when there are even fewer
+    Elroy(u8),
+    /// Eaten ghosts on their way home
+    Eyes,
+    /// Regenerated ghosts on their way out of the house
+    GhostInHouse,
+}
+
+/// The speeds on one level, as fractions of [`BASE_SPEED`]
+struct LevelSpeeds {
+    pac_man: f32,
+    pac_man_frightened: f32,
+    ghost: f32,
+    ghost_frightened: f32,
+    ghost_tunnel: f32,
+    elroy: [f32; 2],
+}
+
+impl LevelSpeeds {
+    fn for_level(level: usize) -> &'static Self {
+        match level {
+            0..=1 => &SPEEDS[0],
+            2..=4 => &SPEEDS[1],
+            5..=20 => &SPEEDS[2],
+            _ => &SPEEDS[3],
+        }
+    }
+}
+
+const SPEEDS: [LevelSpeeds; 4] = [
+    LevelSpeeds {
+        pac_man: 0.8,
+        pac_man_frightened: 0.9,
+        ghost: 0.75,
+        ghost_frightened: 0.5,
+        ghost_tunnel: 0.4,
+        elroy: [0.8, 0.85],
+    },
+    LevelSpeeds {
+        pac_man: 0.9,
+        pac_man_frightened: 0.95,
+        ghost: 0.85,
+        ghost_frightened: 0.55,
+        ghost_tunnel: 0.45,
+        elroy: [0.9, 0.95],
+    },
+    LevelSpeeds {
+        pac_man: 1.0,
+        pac_man_frightened: 1.0,
+        ghost: 0.95,
+        ghost_frightened: 0.6,
+        ghost_tunnel: 0.5,
+        elroy: [1.0, 1.05],
+    },
+    // The arcade ghosts stop being frightened by energizers from here on, so neither speeds up
+    LevelSpeeds {
+        pac_man: 0.9,
+        pac_man_frightened: 0.9,
+        ghost: 0.95,
+        ghost_frightened: 0.6,
+        ghost_tunnel: 0.5,
+        elroy: [1.0, 1.05],
+    },
+];
+
+/// Eyes aren't in the arcade tables, they just look about twice as fast as anything else
+const EYES_SPEED: f32 = 2.0;
+
+/// How many dots can be left before Blinky turns into "Cruise Elroy", for each boost
+fn elroy_dots(level: usize) -> [usize; 2] {
+    match level {
+        0..=1 => [20, 10],
+        2 => [30, 15],
+        3..=5 => [40, 20],
+        6..=8 => [50, 25],
+        9..=11 => [60, 30],
+        12..=14 => [80, 40],
+        15..=18 => [100, 50],
+        _ => [120, 60],
+    }
+}
+
+/// How fast something moves on a level, in pixels per second
+pub fn resolve_speed(level: usize, state: SpeedState) -> Speed {
+    let speeds = LevelSpeeds::for_level(level);
+
+    let fraction = match state {
+        SpeedState::PacMan => speeds.pac_man,
+        SpeedState::PacManFrightened => speeds.pac_man_frightened,
+        SpeedState::Ghost => speeds.ghost,
+        SpeedState::GhostFrightened => speeds.ghost_frightened,
+        // The arcade ghosts move through the house as slowly as through the tunnels
+        SpeedState::GhostInTunnel | SpeedState::GhostInHouse => speeds.ghost_tunnel,
+        SpeedState::Elroy(boost) => speeds.elroy[boost.clamp(1, 2) as usize - 1],
+        SpeedState::Eyes => EYES_SPEED,
+    };
+
+    BASE_SPEED * fraction
+}
+
+/// Which Elroy boost Blinky has with this many dots left, if any
+fn elroy_boost(level: usize, dots_remaining: usize) -> Option<u8> {
+    let [first, second] = elroy_dots(level);
+
+    if dots_remaining <= second {
+        Some(2)
+    } else if dots_remaining <= first {
+        Some(1)
+    } else {
+        None
+    }
+}
+
+/// Whether a location is in one of the tunnels leading to a portal: off the layout, or in the
+/// stretch of corridor between the edge and the first turning
+fn in_tunnel(layout: &Layout, loc: GridLocation) -> bool {
+    if layout.in_tunnel(&loc) {
+        return true;
+    }
+
+    let open = |loc: GridLocation| !layout.collides(&loc);
+    let is_tunnel_row = layout
+        .portals
+        .iter()
+        .any(|portal| portal.0.y == loc.y && portal.1.y == loc.y);
+    if !is_tunnel_row || !open(loc) {
+        return false;
+    }
+
+    // Walk out to the edge, making sure there's nowhere to turn off on the way
+    let dir = if loc.x < WIDTH_TILES as isize / 2 {
+        Dir::Left
+    } else {
+        Dir::Right
+    };
+    let mut current = loc;
+    while layout.in_bounds(&current) {
+        let side_open = open(current.shift(Dir::Up)) || open(current.shift(Dir::Down));
+        if side_open || !open(current) {
+            return false;
+        }
+        current = current.shift(dir);
+    }
+
+    true
+}
+
+type GhostState<'a> = (
+    &'a mut Speed,
+    &'a Ghost,
+    &'a GridLocation,
+    Option<&'a Frightened>,
+    Option<&'a Respawning>,
+    Option<&'a InHouse>,
+);
+
+fn set_speed(
+    level: Res<CurrentLevel>,
+    mode: Res<FrightenedMode>,
+    layout: Res<Layout>,
+    food: Query<(), (With<Food>, Without<Ghost>)>,
+    mut players: Query<&mut Speed, (With<Player>, Without<Ghost>)>,
+    mut ghosts: Query<GhostState>,
+) {
+    let level = **level;
+    let elroy = elroy_boost(level, food.iter().count());
+
+    let player_state = match *mode {
+        FrightenedMode::Enabled => SpeedState::PacManFrightened,
+        FrightenedMode::Disabled => SpeedState::PacMan,
+    };
+    for mut speed in &mut players {
+        update(&mut speed, resolve_speed(level, player_state));
+    }
+
+    for (mut speed, ghost, location, frightened, respawning, in_house) in &mut ghosts {
+        let state = if respawning.is_some() {
+            SpeedState::Eyes
+        } else if in_house.is_some() {
+            SpeedState::GhostInHouse
+        } else if in_tunnel(&layout, *location) {
+            SpeedState::GhostInTunnel
+        } else if frightened.is_some() {
+            SpeedState::GhostFrightened
+        } else {
+            match (ghost.personality, elroy) {
+                (Personality::Blinky, Some(boost)) => SpeedState::Elroy(boost),
+                _ => SpeedState::Ghost,
+            }
+        };
+
+        update(&mut speed, resolve_speed(level, state));
+    }
+}
+
+/// Only touch the speed when it changes, so it isn't marked as changed every frame
+fn update(speed: &mut Mut<Speed>, new_speed: Speed) {
+    if ***speed != *new_speed {
+        **speed = new_speed;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze::Maze;
+
+    fn fraction(level: usize, state: SpeedState) -> f32 {
+        *resolve_speed(level, state) / *BASE_SPEED
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-6, "{a} != {b}");
+    }
+
+    #[test]
+    fn pac_man_follows_the_arcade_level_bands() {
+        for (level, normal, frightened) in [
+            (1, 0.8, 0.9),
+            (2, 0.9, 0.95),
+            (4, 0.9, 0.95),
+            (5, 1.0, 1.0),
+            (20, 1.0, 1.0),
+            (21, 0.9, 0.9),
+            (100, 0.9, 0.9),
+        ] {
+            assert_close(fraction(level, SpeedState::PacMan), normal);
+            assert_close(fraction(level, SpeedState::PacManFrightened), frightened);
+        }
+    }
+
+    #[test]
+    fn ghosts_slow_down_when_frightened_and_slower_still_in_tunnels() {
+        for level in [1, 2, 5, 21] {
+            let ghost = fraction(level, SpeedState::Ghost);
+            let frightened = fraction(level, SpeedState::GhostFrightened);
+            let tunnel = fraction(level, SpeedState::GhostInTunnel);
+
+            assert!(tunnel < frightened && frightened < ghost, "level {level}");
+            assert_close(fraction(level, SpeedState::GhostInHouse), tunnel);
+            assert_close(fraction(level, SpeedState::Eyes), EYES_SPEED);
+        }
+    }
+
+    #[test]
+    fn elroy_speeds_up_twice_as_the_dots_run_out() {
+        assert_eq!(elroy_boost(1, 21), None);
+        assert_eq!(elroy_boost(1, 20), Some(1));
+        assert_eq!(elroy_boost(1, 11), Some(1));
+        assert_eq!(elroy_boost(1, 10), Some(2));
+        assert_eq!(elroy_boost(1, 0), Some(2));
+        assert_eq!(elroy_boost(19, 120), Some(1));
+
+        for level in [1, 2, 5, 21] {
+            let ghost = fraction(level, SpeedState::Ghost);
+            let first = fraction(level, SpeedState::Elroy(1));
+            let second = fraction(level, SpeedState::Elroy(2));
+            assert!(ghost < first && first < second, "level {level}");
+        }
+    }
+
+    #[test]
+    fn tunnels_run_from_the_portals_to_the_first_turning() {
+        let layout = Maze::classic().layout;
+
+        assert!(in_tunnel(&layout, GridLocation { x: -1, y: 18 }));
+        assert!(in_tunnel(&layout, GridLocation { x: 0, y: 18 }));
+        assert!(in_tunnel(
+            &layout,
+            GridLocation {
+                x: WIDTH_TILES as isize - 1,
+                y: 18
+            }
+        ));
+        assert!(!in_tunnel(&layout, GridLocation { x: 6, y: 18 }));
+        assert!(!in_tunnel(&layout, GridLocation { x: 1, y: 1 }));
+    }
+}
@@ -0,0 +1,534 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use bevy::prelude::*;
+
+use crate::actor::movement::Dir;
+use crate::actor::player::SyncedInput;
+use crate::from_env::FromEnv;
+
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Controls::from_env())
+            .add_system(toggle_pause);
+    }
+}
+
+/// Something the player can do by pressing a key
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    /// Steers the second Pac-Man, when there's more than one
+    Up2,
+    Down2,
+    Left2,
+    Right2,
+    Pause,
+    Quit,
+    /// Starts a new game after a game over, and confirms initials for the high score table
+    Start,
+    /// Turns debug mode on and off
+    Debug,
+    Die,
+    Frighten,
+    /// Swaps the ghosts' brains for ones that wander at random, in debug mode
+    Wander,
+    /// Cycles through the autopilots, in debug mode
+    Autopilot,
+    EatBlinky,
+    EatPinky,
+    EatInky,
+    EatClyde,
+    /// Saves the game to a file, in debug mode
+    QuickSave,
+    /// Loads the game saved with [`Action::QuickSave`], in debug mode
+    QuickLoad,
+    /// Opens the maze editor in debug mode, or test-plays the maze from inside it
+    Edit,
+    /// Test-plays the maze from inside the editor
+    TestPlay,
+    /// Saves the maze from inside the editor
+    SaveMaze,
+    /// Picks the tile the editor paints with
+    BrushWall,
+    BrushDot,
+    BrushEnergizer,
+    BrushDoor,
+    BrushPacMan,
+    /// The spawn tiles the classic ghosts start on
+    BrushBlinky,
+    BrushPinky,
+    BrushInky,
+    BrushClyde,
+    BrushErase,
+}
+
+impl Action {
+    pub const ALL: [Action; 35] = [
+        Action::Up,
+        Action::Down,
+        Action::Left,
+        Action::Right,
+        Action::Up2,
+        Action::Down2,
+        Action::Left2,
+        Action::Right2,
+        Action::Pause,
+        Action::Quit,
+        Action::Start,
+        Action::Debug,
+        Action::Die,
+        Action::Frighten,
+        Action::Wander,
+        Action::Autopilot,
+        Action::EatBlinky,
+        Action::EatPinky,
+        Action::EatInky,
+        Action::EatClyde,
+        Action::QuickSave,
+        Action::QuickLoad,
+        Action::Edit,
+        Action::TestPlay,
+        Action::SaveMaze,
+        Action::BrushWall,
+        Action::BrushDot,
+        Action::BrushEnergizer,
+        Action::BrushDoor,
+        Action::BrushPacMan,
+        Action::BrushBlinky,
+        Action::BrushPinky,
+        Action::BrushInky,
+        Action::BrushClyde,
+        Action::BrushErase,
+    ];
+
+    /// The name used in controls files
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::Left => "left",
+            Action::Right => "right",
+            Action::Up2 => "up-2",
+            Action::Down2 => "down-2",
+            Action::Left2 => "left-2",
+            Action::Right2 => "right-2",
+            Action::Pause => "pause",
+            Action::Quit => "quit",
+            Action::Start => "start",
+            Action::Debug => "debug",
+            Action::Die => "die",
+            Action::Frighten => "frighten",
+            Action::Wander => "wander",
+            Action::Autopilot => "autopilot",
+            Action::EatBlinky => "eat-blinky",
+            Action::EatPinky => "eat-pinky",
+            Action::EatInky => "eat-inky",
+            Action::EatClyde => "eat-clyde",
+            Action::QuickSave => "quick-save",
+            Action::QuickLoad => "quick-load",
+            Action::Edit => "edit",
+            Action::TestPlay => "test-play",
+            Action::SaveMaze => "save-maze",
+            Action::BrushWall => "brush-wall",
+            Action::BrushDot => "brush-dot",
+            Action::BrushEnergizer => "brush-energizer",
+            Action::BrushDoor => "brush-door",
+            Action::BrushPacMan => "brush-pac-man",
+            Action::BrushBlinky => "brush-blinky",
+            Action::BrushPinky => "brush-pinky",
+            Action::BrushInky => "brush-inky",
+            Action::BrushClyde => "brush-clyde",
+            Action::BrushErase => "brush-erase",
+        }
+    }
+
+    /// The action that steers a player in a direction, if they can use the keyboard
+    pub fn steer(player: usize, dir: Dir) -> Option<Action> {
+        let action = match (player, dir) {
+            (0, Dir::Up) => Action::Up,
+            (0, Dir::Down) => Action::Down,
+            (0, Dir::Left) => Action::Left,
+            (0, Dir::Right) => Action::Right,
+            (1, Dir::Up) => Action::Up2,
+            (1, Dir::Down) => Action::Down2,
+            (1, Dir::Left) => Action::Left2,
+            (1, Dir::Right) => Action::Right2,
+            _ => return None,
+        };
+
+        Some(action)
+    }
+}
+
+impl FromStr for Action {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_lowercase();
+        Action::ALL
+            .into_iter()
+            .find(|action| action.name() == s)
+            .ok_or(())
+    }
+}
+
+/// Which keys do what.
+///
+/// Controls are stored as text files with one action per line, followed by its keys:
+/// `action key [key...]`, e.g. `up W Up`. Keys are named like Bevy's [`KeyCode`]s.
+/// Blank lines and lines starting with `#` are ignored, and actions that aren't listed keep
+/// their default keys.
+#[derive(Resource, Clone, Debug)]
+pub struct Controls {
+    bindings: HashMap<Action, Vec<KeyCode>>,
+}
+
+impl Controls {
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn bind(&mut self, action: Action, keys: Vec<KeyCode>) {
+        self.bindings.insert(action, keys);
+    }
+
+    pub fn pressed(&self, input: &Input<KeyCode>, action: Action) -> bool {
+        input.any_pressed(self.keys(action).iter().copied())
+    }
+
+    pub fn just_pressed(&self, input: &Input<KeyCode>, action: Action) -> bool {
+        input.any_just_pressed(self.keys(action).iter().copied())
+    }
+
+    /// Rebinds the actions listed in a controls file, leaving the rest as they are
+    fn with_file(mut self, s: &str) -> Result<Self, ParseControlsError> {
+        let lines = s
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+        for line in lines {
+            let invalid = || ParseControlsError(line.to_string());
+            let mut fields = line.split_whitespace();
+
+            let action = fields.next().ok_or_else(invalid)?;
+            let action = action.parse().map_err(|_| invalid())?;
+            let keys = parse_keys(fields).ok_or_else(invalid)?;
+
+            self.bind(action, keys);
+        }
+
+        Ok(self)
+    }
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        let empty = Self {
+            bindings: HashMap::new(),
+        };
+        empty
+            .with_file(include_str!("../assets/controls/default.controls"))
+            .unwrap_or_else(|err| panic!("Invalid default controls: {err}"))
+    }
+}
+
+impl FromEnv for Controls {
+    fn with_env_overrides(self) -> Self {
+        let mut controls = match get_controls() {
+            None => self,
+            Some(path) => {
+                let file = std::fs::read_to_string(&path)
+                    .unwrap_or_else(|err| panic!("Can't read controls {path}: {err}"));
+                self.with_file(&file)
+                    .unwrap_or_else(|err| panic!("Invalid controls: {err}"))
+            }
+        };
+
+        for action in Action::ALL {
+            if let Some(keys) = get_control(action) {
+                controls.bind(action, keys);
+            }
+        }
+
+        controls
+    }
+}
+
+/// The path to a controls file
+fn get_controls() -> Option<String> {
+    std::env::var("CONTROLS").ok()
+}
+
+/// Keys separated by commas, e.g. `CONTROL_EAT_BLINKY=B,Key1`
+fn get_control(action: Action) -> Option<Vec<KeyCode>> {
+    let var = format!("CONTROL_{}", action.name().replace('-', "_").to_uppercase());
+    let keys = std::env::var(var).ok()?;
+    parse_keys(keys.split(','))
+}
+
+fn parse_keys<'a>(names: impl Iterator<Item = &'a str>) -> Option<Vec<KeyCode>> {
+    let keys: Option<Vec<KeyCode>> = names.map(|name| parse_key(name.trim())).collect();
+    keys.filter(|keys| !keys.is_empty())
+}
+
+#[derive(Debug)]
+pub struct ParseControlsError(String);
+
+impl fmt::Display for ParseControlsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected 'action key [key...]' but found {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseControlsError {}
+
+/// The keys that can be bound, by the name of their [`KeyCode`]
+fn parse_key(name: &str) -> Option<KeyCode> {
+    use KeyCode::*;
+
+    let key = match name {
+        "A" => A,
+        "B" => B,
+        "C" => C,
+        "D" => D,
+        "E" => E,
+        "F" => F,
+        "G" => G,
+        "H" => H,
+        "I" => I,
+        "J" => J,
+        "K" => K,
+        "L" => L,
+        "M" => M,
+        "N" => N,
+        "O" => O,
+        "P" => P,
+        "Q" => Q,
+        "R" => R,
+        "S" => S,
+        "T" => T,
+        "U" => U,
+        "V" => V,
+        "W" => W,
+        "X" => X,
+        "Y" => Y,
+        "Z" => Z,
+        "Key0" => Key0,
+        "Key1" => Key1,
+        "Key2" => Key2,
+        "Key3" => Key3,
+        "Key4" => Key4,
+        "Key5" => Key5,
+        "Key6" => Key6,
+        "Key7" => Key7,
+        "Key8" => Key8,
+        "Key9" => Key9,
+        "Numpad0" => Numpad0,
+        "Numpad1" => Numpad1,
+        "Numpad2" => Numpad2,
+        "Numpad3" => Numpad3,
+        "Numpad4" => Numpad4,
+        "Numpad5" => Numpad5,
+        "Numpad6" => Numpad6,
+        "Numpad7" => Numpad7,
+        "Numpad8" => Numpad8,
+        "Numpad9" => Numpad9,
+        "F1" => F1,
+        "F2" => F2,
+        "F3" => F3,
+        "F4" => F4,
+        "F5" => F5,
+        "F6" => F6,
+        "F7" => F7,
+        "F8" => F8,
+        "F9" => F9,
+        "F10" => F10,
+        "F11" => F11,
+        "F12" => F12,
+        "Up" => Up,
+        "Down" => Down,
+        "Left" => Left,
+        "Right" => Right,
+        "Escape" => Escape,
+        "Space" => Space,
+        "Return" => Return,
+        "Tab" => Tab,
+        "Back" => Back,
+        "Delete" => Delete,
+        "Insert" => Insert,
+        "Home" => Home,
+        "End" => End,
+        "PageUp" => PageUp,
+        "PageDown" => PageDown,
+        "LShift" => LShift,
+        "RShift" => RShift,
+        "LControl" => LControl,
+        "RControl" => RControl,
+        "LAlt" => LAlt,
+        "RAlt" => RAlt,
+        "Grave" => Grave,
+        "Minus" => Minus,
+        "Equals" => Equals,
+        "LBracket" => LBracket,
+        "RBracket" => RBracket,
+        "Backslash" => Backslash,
+        "Semicolon" => Semicolon,
+        "Apostrophe" => Apostrophe,
+        "Comma" => Comma,
+        "Period" => Period,
+        "Slash" => Slash,
+        _ => return None,
+    };
+
+    Some(key)
+}
+
+fn toggle_pause(
+    controls: Res<Controls>,
+    input: Res<Input<KeyCode>>,
+    synced: Option<Res<SyncedInput>>,
+    mut time: ResMut<Time>,
+) {
+    // Whoever's steering from elsewhere decides when the game moves on, e.g. the other end of a
+    // network game would carry on without us
+    if synced.is_some() || !controls.just_pressed(&input, Action::Pause) {
+        return;
+    }
+
+    if time.is_paused() {
+        time.unpause();
+    } else {
+        time.pause();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty() -> Controls {
+        Controls {
+            bindings: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn binds_each_line() {
+        let controls = empty().with_file("up W Up\nsave-maze F2").unwrap();
+
+        assert_eq!(controls.keys(Action::Up), [KeyCode::W, KeyCode::Up]);
+        assert_eq!(controls.keys(Action::SaveMaze), [KeyCode::F2]);
+        assert_eq!(controls.keys(Action::Down), []);
+    }
+
+    #[test]
+    fn skips_comments_and_blank_lines() {
+        let controls = empty()
+            .with_file("# Arrows\n\n   \n  left   Left  \n#right Right")
+            .unwrap();
+
+        assert_eq!(controls.keys(Action::Left), [KeyCode::Left]);
+        assert_eq!(controls.keys(Action::Right), []);
+    }
+
+    #[test]
+    fn keeps_unlisted_actions() {
+        let controls = Controls::default().with_file("pause P").unwrap();
+
+        assert_eq!(controls.keys(Action::Pause), [KeyCode::P]);
+        assert_eq!(controls.keys(Action::TestPlay), [KeyCode::Return]);
+    }
+
+    #[test]
+    fn actions_ignore_case() {
+        let controls = empty().with_file("EAT-Blinky B").unwrap();
+
+        assert_eq!(controls.keys(Action::EatBlinky), [KeyCode::B]);
+    }
+
+    #[test]
+    fn rejects_bad_lines() {
+        for line in ["jump Space", "up Hyperspace", "up", "up W Nope"] {
+            let err = empty().with_file(line).unwrap_err();
+            assert_eq!(err.0, line);
+        }
+    }
+
+    #[test]
+    fn parses_keys() {
+        assert_eq!(
+            parse_keys("B, Key1".split(',')),
+            Some(vec![KeyCode::B, KeyCode::Key1])
+        );
+        assert_eq!(parse_keys("".split_whitespace()), None);
+        assert_eq!(parse_keys("B,b".split(',')), None);
+    }
+
+    #[test]
+    fn the_default_controls_bind_every_action_for_one_player() {
+        let controls = Controls::default();
+        let second_player =
+            [Dir::Up, Dir::Down, Dir::Left, Dir::Right].map(|dir| Action::steer(1, dir));
+
+        for action in Action::ALL {
+            if !second_player.contains(&Some(action)) {
+                assert!(!controls.keys(action).is_empty(), "{action:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn the_editor_keys_dont_clash_with_the_debug_keys() {
+        use Action::*;
+
+        // The editor only opens in debug mode, so both sets of keys work at the same time
+        let actions = [
+            Debug,
+            Die,
+            Frighten,
+            Wander,
+            Autopilot,
+            EatBlinky,
+            EatPinky,
+            EatInky,
+            EatClyde,
+            Edit,
+            TestPlay,
+            SaveMaze,
+            BrushWall,
+            BrushDot,
+            BrushEnergizer,
+            BrushDoor,
+            BrushPacMan,
+            BrushBlinky,
+            BrushPinky,
+            BrushInky,
+            BrushClyde,
+            BrushErase,
+        ];
+
+        for file in [
+            "",
+            include_str!("../assets/controls/wasd.controls"),
+            include_str!("../assets/controls/two-players.controls"),
+        ] {
+            let controls = Controls::default().with_file(file).unwrap();
+            let mut keys = vec![];
+
+            for action in actions {
+                for key in controls.keys(action) {
+                    assert!(!keys.contains(key), "{key:?} is bound twice");
+                    keys.push(*key);
+                }
+            }
+        }
+    }
+}
@@ -1,23 +1,36 @@
+use std::path::PathBuf;
+
 use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
 use bevy::prelude::*;
 use bevy_inspector_egui::{RegisterInspectable, WorldInspectorParams, WorldInspectorPlugin};
 use bevy_prototype_debug_lines::{DebugLines, DebugLinesPlugin};
 
-use crate::actor::ghost::{Blinky, Clyde, Ghost, Inky, Pinky, SetTarget, Target};
+use crate::actor::autopilot::Autopilot;
+use crate::actor::ghost::{
+    chased_player, predict_path, ActorState, Blinky, Brain, Chases, DistanceMaps, Frightened,
+    Ghost, Heading, Navigation, Personality, SetTarget, Target, Targeting, Wander,
+};
 use crate::actor::mode::{FrightenedMode, SetMode};
 use crate::actor::movement::{Dir, NextDir, SetDir, SetNextDir};
-use crate::actor::player::{PlayerDeath, PlayerDied};
+use crate::actor::player::{Player, PlayerDeath, PlayerDied, PlayerNumber};
+use crate::controls::{Action, Controls};
 use crate::food::{Eat, Food, WriteEatEvent};
 use crate::from_env::{ExecutionOrderAmbiguitiesPlugin, FromEnv};
 use crate::grid::{Grid, GridLocation, SetGridLocation};
+use crate::layout::{ActorKind, Layout, Portal};
 use crate::level::GRID;
+use crate::maze::CurrentMaze;
+use crate::snapshot::Snapshot;
+
+/// How far ahead to draw the route each ghost is going to take
+const PREDICTED_TILES: usize = 24;
 
 pub struct InspectorPlugin;
 
 impl Plugin for InspectorPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(LogDiagnosticsPlugin::from_env())
-            .add_plugin(FrameTimeDiagnosticsPlugin::default())
+            .add_plugin(FrameTimeDiagnosticsPlugin)
             .add_plugin(WorldInspectorPlugin::new())
             .add_plugin(DebugLinesPlugin::default())
             .add_plugin(ExecutionOrderAmbiguitiesPlugin)
@@ -33,6 +46,12 @@ impl Plugin for InspectorPlugin {
                     .label(DebugSystem)
                     // We don't care much about the ordering of debug stuff
                     .with_system(toggle_inspector.ambiguous_with(DebugSystem))
+                    .with_system(
+                        toggle_wandering_ghosts
+                            .before(SetTarget)
+                            .ambiguous_with(DebugSystem),
+                    )
+                    .with_system(toggle_autopilot.ambiguous_with(DebugSystem))
                     .with_system(
                         trigger_eat_ghost
                             .label(WriteEatEvent)
@@ -40,6 +59,11 @@ impl Plugin for InspectorPlugin {
                             .ambiguous_with(DebugSystem),
                     )
                     .with_system(draw_grid.ambiguous_with(DebugSystem))
+                    .with_system(
+                        draw_maze_features
+                            .after(draw_grid)
+                            .ambiguous_with(DebugSystem),
+                    )
                     .with_system(draw_dir.after(SetDir).ambiguous_with(DebugSystem))
                     .with_system(
                         draw_next_dir
@@ -53,10 +77,23 @@ impl Plugin for InspectorPlugin {
                             .after(SetTarget)
                             .after(draw_grid)
                             .ambiguous_with(DebugSystem),
+                    )
+                    .with_system(
+                        draw_predicted_path
+                            .after(SetTarget)
+                            .after(SetNextDir)
+                            .ambiguous_with(DebugSystem),
+                    )
+                    .with_system(
+                        draw_target_construction
+                            .after(SetTarget)
+                            .ambiguous_with(DebugSystem),
                     ),
             )
             .add_system(trigger_death.label(PlayerDeath).ambiguous_with(PlayerDeath))
             .add_system(trigger_frightened.before(SetMode))
+            // Between frames, once everything else has had its say
+            .add_system_to_stage(CoreStage::Last, quick_save_and_load.at_end())
             .register_inspectable::<NextDir>()
             .register_inspectable::<Dir>()
             .register_inspectable::<GridLocation>();
@@ -64,17 +101,46 @@ impl Plugin for InspectorPlugin {
 }
 
 #[derive(Resource, Default)]
-struct DebugMode(bool);
+pub struct DebugMode(pub bool);
 
 #[derive(SystemLabel)]
 struct DebugSystem;
 
-fn toggle_debug_mode(mut debug_mode: ResMut<DebugMode>, keyboard_input: Res<Input<KeyCode>>) {
-    if keyboard_input.just_pressed(KeyCode::Grave) {
+fn toggle_debug_mode(
+    mut debug_mode: ResMut<DebugMode>,
+    controls: Res<Controls>,
+    keyboard_input: Res<Input<KeyCode>>,
+) {
+    if controls.just_pressed(&keyboard_input, Action::Debug) {
         debug_mode.0 = !debug_mode.0;
     }
 }
 
+/// Saves the whole game to a file, or loads it back, so the same situation can be tried again
+fn quick_save_and_load(world: &mut World) {
+    if !world.resource::<DebugMode>().0 {
+        return;
+    }
+
+    let controls = world.resource::<Controls>();
+    let keyboard_input = world.resource::<Input<KeyCode>>();
+    let save = controls.just_pressed(keyboard_input, Action::QuickSave);
+    let load = controls.just_pressed(keyboard_input, Action::QuickLoad);
+    let path = get_snapshot_file();
+
+    if save {
+        match Snapshot::save(world, &path) {
+            Ok(()) => info!("Saved the game to {}", path.display()),
+            Err(err) => error!("Couldn't save the game to {}: {err}", path.display()),
+        }
+    } else if load {
+        match Snapshot::load(world, &path) {
+            Ok(()) => info!("Loaded the game from {}", path.display()),
+            Err(err) => error!("Couldn't load the game from {}: {err}", path.display()),
+        }
+    }
+}
+
 fn toggle_inspector(
     debug_mode: Res<DebugMode>,
     mut inspector_params: ResMut<WorldInspectorParams>,
@@ -84,52 +150,91 @@ fn toggle_inspector(
     }
 }
 
-fn trigger_death(keyboard_input: Res<Input<KeyCode>>, mut death_events: EventWriter<PlayerDied>) {
-    if keyboard_input.just_pressed(KeyCode::Period) {
-        death_events.send(PlayerDied);
+/// Swap every ghost's brain for one that wanders at random, and back again
+fn toggle_wandering_ghosts(
+    mut commands: Commands,
+    debug_mode: Res<DebugMode>,
+    controls: Res<Controls>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut wandering: Local<bool>,
+    ghosts: Query<(Entity, &Ghost), With<Brain>>,
+) {
+    if !debug_mode.0 || !controls.just_pressed(&keyboard_input, Action::Wander) {
+        return;
+    }
+
+    *wandering = !*wandering;
+
+    for (entity, ghost) in &ghosts {
+        let brain = if *wandering {
+            Brain::new(Wander)
+        } else {
+            ghost.personality.brain()
+        };
+        commands.entity(entity).insert(brain);
+    }
+}
+
+/// Cycle through the autopilots, then back to the keyboard
+fn toggle_autopilot(
+    debug_mode: Res<DebugMode>,
+    controls: Res<Controls>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut autopilot: ResMut<Autopilot>,
+) {
+    if debug_mode.0 && controls.just_pressed(&keyboard_input, Action::Autopilot) {
+        *autopilot = autopilot.next();
+        info!("Autopilot: {:?}", *autopilot);
+    }
+}
+
+fn trigger_death(
+    controls: Res<Controls>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut death_events: EventWriter<PlayerDied>,
+) {
+    if controls.just_pressed(&keyboard_input, Action::Die) {
+        death_events.send(PlayerDied { ghost: None });
     }
 }
 
-fn trigger_frightened(keyboard_input: Res<Input<KeyCode>>, mut mode: ResMut<FrightenedMode>) {
-    if keyboard_input.just_pressed(KeyCode::Comma) {
+fn trigger_frightened(
+    controls: Res<Controls>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut mode: ResMut<FrightenedMode>,
+) {
+    if controls.just_pressed(&keyboard_input, Action::Frighten) {
         *mode = FrightenedMode::Enabled;
     }
 }
 
 fn trigger_eat_ghost(
     debug_mode: Res<DebugMode>,
+    controls: Res<Controls>,
     keyboard_input: Res<Input<KeyCode>>,
-    blinky: Query<Entity, (With<Blinky>, With<Food>)>,
-    pinky: Query<Entity, (With<Pinky>, With<Food>)>,
-    inky: Query<Entity, (With<Inky>, With<Food>)>,
-    clyde: Query<Entity, (With<Clyde>, With<Food>)>,
+    ghosts: Query<(Entity, &Ghost), With<Food>>,
     mut eat_events: EventWriter<Eat>,
 ) {
     if !debug_mode.0 {
         return;
     }
 
-    if keyboard_input.just_pressed(KeyCode::B) {
-        for ghost in &blinky {
-            eat_events.send(Eat(ghost));
-        }
-    }
+    let actions = [
+        (Action::EatBlinky, Personality::Blinky),
+        (Action::EatPinky, Personality::Pinky),
+        (Action::EatInky, Personality::Inky),
+        (Action::EatClyde, Personality::Clyde),
+    ];
 
-    if keyboard_input.just_pressed(KeyCode::P) {
-        for ghost in &pinky {
-            eat_events.send(Eat(ghost));
+    for (action, personality) in actions {
+        if !controls.just_pressed(&keyboard_input, action) {
+            continue;
         }
-    }
-
-    if keyboard_input.just_pressed(KeyCode::I) {
-        for ghost in &inky {
-            eat_events.send(Eat(ghost));
-        }
-    }
 
-    if keyboard_input.just_pressed(KeyCode::C) {
-        for ghost in &clyde {
-            eat_events.send(Eat(ghost));
+        for (entity, ghost) in &ghosts {
+            if ghost.personality == personality {
+                eat_events.send(Eat(entity, None));
+            }
         }
     }
 }
@@ -153,6 +258,32 @@ fn draw_grid(debug_mode: Res<DebugMode>, mut lines: ResMut<DebugLines>) {
     }
 }
 
+fn draw_maze_features(
+    debug_mode: Res<DebugMode>,
+    mut lines: ResMut<DebugLines>,
+    maze: Res<CurrentMaze>,
+) {
+    if !debug_mode.0 {
+        return;
+    }
+
+    for Portal(a, b) in &maze.layout.portals {
+        let start = GRID.to_vec2(*a).extend(0.0);
+        let end = GRID.to_vec2(*b).extend(0.0);
+        lines.line_colored(start, end, 0.0, Color::GREEN);
+    }
+
+    for entry in &maze.fruit_entries {
+        let entry = GRID.to_vec2(*entry).extend(0.0);
+        let x = (Vec2::X * GRID.size / 2.0).extend(0.0);
+        let y = (Vec2::Y * GRID.size / 2.0).extend(0.0);
+        lines.line_colored(entry - x - y, entry + x - y, 0.0, Color::GREEN);
+        lines.line_colored(entry + x - y, entry + x + y, 0.0, Color::GREEN);
+        lines.line_colored(entry + x + y, entry - x + y, 0.0, Color::GREEN);
+        lines.line_colored(entry - x + y, entry - x - y, 0.0, Color::GREEN);
+    }
+}
+
 fn draw_target(
     debug_mode: Res<DebugMode>,
     mut lines: ResMut<DebugLines>,
@@ -210,3 +341,139 @@ fn draw_next_dir(
         lines.line(start, end, 0.0);
     }
 }
+
+type GhostRoute<'a> = (
+    &'a Ghost,
+    &'a Grid,
+    &'a GridLocation,
+    &'a Dir,
+    &'a NextDir,
+    &'a Target,
+    &'a ActorKind,
+    Option<&'a Navigation>,
+);
+
+/// Frightened and wandering ghosts don't head for their target
+type FollowingTarget = (Without<Frightened>, Without<Heading>);
+
+/// Where each ghost will go next, if its target stays put
+fn draw_predicted_path(
+    debug_mode: Res<DebugMode>,
+    mut lines: ResMut<DebugLines>,
+    layout: Res<Layout>,
+    targeting: Res<Targeting>,
+    // Kept separate from the ghosts' own, so predicting doesn't get in the way of the game
+    mut distance_maps: Local<DistanceMaps>,
+    query: Query<GhostRoute, FollowingTarget>,
+) {
+    if layout.is_changed() {
+        distance_maps.clear();
+    }
+    if !debug_mode.0 {
+        return;
+    }
+
+    for (ghost, grid, location, dir, next_dir, target, kind, navigation) in &query {
+        let path = predict_path(
+            &layout,
+            &targeting,
+            &mut distance_maps,
+            *kind,
+            navigation.copied(),
+            *location,
+            *dir,
+            **next_dir,
+            *target,
+            PREDICTED_TILES,
+        );
+
+        let mut from = *location;
+        for to in path {
+            // Don't draw a line across the screen when going through a portal
+            if from
+                .to_unscaled_vec2()
+                .distance_squared(to.to_unscaled_vec2())
+                <= 1.0
+            {
+                let start = grid.to_vec2(from).extend(0.0);
+                let end = grid.to_vec2(to).extend(0.0);
+                lines.line_colored(start, end, 0.0, ghost.color());
+            }
+            from = to;
+        }
+    }
+}
+
+/// How Clyde and Inky work out their chase targets: the circle around Clyde that Pac-Man has
+/// to be outside of to be chased, and the line from Blinky through a point ahead of Pac-Man
+/// that Inky doubles to get his target
+fn draw_target_construction(
+    debug_mode: Res<DebugMode>,
+    mut lines: ResMut<DebugLines>,
+    targeting: Res<Targeting>,
+    ghosts: Query<(&Ghost, &GridLocation, Option<&Chases>)>,
+    players: Query<(&PlayerNumber, &GridLocation, &Dir), With<Player>>,
+) {
+    if !debug_mode.0 {
+        return;
+    }
+
+    let blinky = ghosts
+        .iter()
+        .find(|(ghost, _, _)| ghost.personality == Personality::Blinky)
+        .map(|(_, location, _)| *location);
+    let players: Vec<(PlayerNumber, ActorState)> = players
+        .iter()
+        .map(|(number, location, dir)| {
+            let state = ActorState {
+                location: *location,
+                dir: *dir,
+            };
+            (*number, state)
+        })
+        .collect();
+
+    for (ghost, location, chases) in &ghosts {
+        match ghost.personality {
+            Personality::Clyde => {
+                let center = GRID.to_vec2(*location);
+                let radius = GRID.size * 8.0;
+                let points: Vec<Vec3> = (0..=32)
+                    .map(|i| {
+                        let angle = i as f32 / 32.0 * std::f32::consts::TAU;
+                        (center + Vec2::from_angle(angle) * radius).extend(0.0)
+                    })
+                    .collect();
+                for pair in points.windows(2) {
+                    lines.line_colored(pair[0], pair[1], 0.0, ghost.color());
+                }
+            }
+            Personality::Inky => {
+                let player = chased_player(&players, *location, chases);
+                let (blinky, player) = match (blinky, player) {
+                    (Some(blinky), Some(player)) => (blinky, player),
+                    _ => continue,
+                };
+                let pivot = targeting.accuracy.ahead(player.location, player.dir, 2);
+                let target = GridLocation {
+                    x: 2 * pivot.x - blinky.x,
+                    y: 2 * pivot.y - blinky.y,
+                };
+
+                let blinky = GRID.to_vec2(blinky).extend(0.0);
+                let pivot_vec = GRID.to_vec2(pivot).extend(0.0);
+                let target = GRID.to_vec2(target).extend(0.0);
+                lines.line_colored(blinky, pivot_vec, 0.0, Blinky::COLOR);
+                lines.line_colored(pivot_vec, target, 0.0, ghost.color());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Where quick-saves go, e.g. `SNAPSHOT_FILE=saves/ghost-house.json`
+fn get_snapshot_file() -> PathBuf {
+    std::env::var_os("SNAPSHOT_FILE")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("quick-save.json"))
+}
@@ -0,0 +1,326 @@
+use bevy::prelude::*;
+use bevy_prototype_debug_lines::DebugLines;
+
+use crate::actor::ghost::{GhostSpawner, GhostSpec};
+use crate::controls::{Action, Controls};
+use crate::diagnostics::DebugMode;
+use crate::grid::{GridBundle, GridLocation, Layer};
+use crate::layout::{Layout, Tile};
+use crate::level::{RestartLevel, UnloadLevel, GRID, HEIGHT_TILES, WIDTH_TILES};
+use crate::maze::{get_maze_file, validate, CurrentMaze, Maze, Problem};
+use crate::text::{SetTextSprites, TextBundle, TextSprites};
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Editor>()
+            .add_startup_system(setup_status_display)
+            .add_system(toggle_editor.label(ToggleEditor))
+            .add_system_set(
+                SystemSet::new()
+                    .after(ToggleEditor)
+                    .with_system(choose_brush)
+                    .with_system(paint.label(EditLayout))
+                    .with_system(check_layout.after(EditLayout))
+                    .with_system(save_maze.after(EditLayout))
+                    .with_system(draw_layout.after(check_layout))
+                    .with_system(
+                        update_status_display
+                            .after(check_layout)
+                            .after(choose_brush)
+                            .before(SetTextSprites),
+                    ),
+            );
+    }
+}
+
+#[derive(SystemLabel)]
+struct ToggleEditor;
+
+#[derive(SystemLabel)]
+struct EditLayout;
+
+const BRUSHES: [(Action, Option<Tile>); 10] = [
+    (Action::BrushWall, Some(Tile::Wall)),
+    (Action::BrushDot, Some(Tile::Dot)),
+    (Action::BrushEnergizer, Some(Tile::Energizer)),
+    (Action::BrushDoor, Some(Tile::Door)),
+    (Action::BrushPacMan, Some(Tile::PacMan)),
+    (Action::BrushBlinky, Some(Tile::Ghost('B'))),
+    (Action::BrushPinky, Some(Tile::Ghost('P'))),
+    (Action::BrushInky, Some(Tile::Ghost('I'))),
+    (Action::BrushClyde, Some(Tile::Ghost('C'))),
+    (Action::BrushErase, None),
+];
+
+/// Paints tiles onto the layout with the mouse, while the level is unloaded.
+/// Left click paints the brush (chosen with the number keys by default) and right click erases.
+#[derive(Resource)]
+pub struct Editor {
+    enabled: bool,
+    brush: Option<Tile>,
+    problems: Vec<Problem>,
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            brush: Some(Tile::Wall),
+            problems: vec![],
+        }
+    }
+}
+
+#[derive(Component)]
+struct EditorStatusDisplay;
+
+fn setup_status_display(mut commands: Commands) {
+    commands.spawn(TextBundle::default()).insert((
+        GridBundle::new(GRID, GridLocation { x: 0, y: 1 }, Layer::UI),
+        Name::new("Editor Status"),
+        EditorStatusDisplay,
+    ));
+}
+
+/// Enter the editor with `edit` (E) in debug mode. Pressing it again, or `test-play` (Enter),
+/// test-plays the layout.
+fn toggle_editor(
+    debug_mode: Res<DebugMode>,
+    controls: Res<Controls>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut editor: ResMut<Editor>,
+    mut unload_events: EventWriter<UnloadLevel>,
+    mut restart_events: EventWriter<RestartLevel>,
+) {
+    if !editor.enabled {
+        if debug_mode.0 && controls.just_pressed(&keyboard_input, Action::Edit) {
+            info!("Editing layout");
+            editor.enabled = true;
+            unload_events.send(UnloadLevel);
+        }
+        return;
+    }
+
+    let test_play = [Action::Edit, Action::TestPlay]
+        .into_iter()
+        .any(|action| controls.just_pressed(&keyboard_input, action));
+    if !test_play {
+        return;
+    }
+
+    if let Some(problem) = editor.problems.first() {
+        warn!("Can't play layout: {}", problem);
+        return;
+    }
+
+    info!("Playing edited layout");
+    editor.enabled = false;
+    restart_events.send(RestartLevel);
+}
+
+fn choose_brush(
+    controls: Res<Controls>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut editor: ResMut<Editor>,
+) {
+    if !editor.enabled {
+        return;
+    }
+
+    for (action, brush) in BRUSHES {
+        if controls.just_pressed(&keyboard_input, action) && editor.brush != brush {
+            editor.brush = brush;
+        }
+    }
+}
+
+fn paint(
+    editor: Res<Editor>,
+    mouse_input: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    mut layout: ResMut<Layout>,
+    mut maze: ResMut<CurrentMaze>,
+) {
+    if !editor.enabled {
+        return;
+    }
+
+    let tile = if mouse_input.pressed(MouseButton::Left) {
+        editor.brush
+    } else if mouse_input.pressed(MouseButton::Right) {
+        None
+    } else {
+        return;
+    };
+
+    let location = match cursor_location(&windows, &cameras) {
+        Some(location) => location,
+        None => return,
+    };
+
+    if layout.get(&location) == Ok(tile) {
+        return;
+    }
+
+    layout.set(&location, tile);
+
+    // Any pre-drawn artwork won't match anymore
+    if maze.artwork.is_some() {
+        maze.artwork = None;
+    }
+}
+
+fn cursor_location(
+    windows: &Windows,
+    cameras: &Query<(&Camera, &GlobalTransform)>,
+) -> Option<GridLocation> {
+    let cursor = windows.get_primary()?.cursor_position()?;
+    let (camera, camera_transform) = cameras.get_single().ok()?;
+    let position = camera.viewport_to_world(camera_transform, cursor)?.origin;
+    let location = GRID.to_grid_location(position.truncate());
+
+    let in_bounds = (0..WIDTH_TILES as isize).contains(&location.x)
+        && (0..HEIGHT_TILES as isize).contains(&location.y);
+    in_bounds.then_some(location)
+}
+
+fn check_layout(layout: Res<Layout>, mut editor: ResMut<Editor>) {
+    if !editor.enabled || !(layout.is_changed() || editor.is_changed()) {
+        return;
+    }
+
+    let problems = validate(&layout);
+    if editor.problems != problems {
+        editor.problems = problems;
+    }
+}
+
+/// Save with `save-maze` (S), to the file in `MAZE_FILE` or `custom.maze`
+fn save_maze(
+    editor: Res<Editor>,
+    controls: Res<Controls>,
+    keyboard_input: Res<Input<KeyCode>>,
+    layout: Res<Layout>,
+    maze: Res<CurrentMaze>,
+) {
+    if !editor.enabled || !controls.just_pressed(&keyboard_input, Action::SaveMaze) {
+        return;
+    }
+
+    let path = get_maze_file().unwrap_or_else(|| "custom.maze".to_string());
+    let maze = Maze {
+        layout: layout.clone(),
+        ..maze.0.clone()
+    };
+
+    match std::fs::write(&path, maze.to_string()) {
+        Ok(()) => info!("Saved maze to {}", path),
+        Err(err) => error!("Failed to save maze to {}: {}", path, err),
+    }
+
+    for problem in &editor.problems {
+        warn!("Saved maze has a problem: {}", problem);
+    }
+}
+
+fn draw_layout(
+    editor: Res<Editor>,
+    layout: Res<Layout>,
+    maze: Res<CurrentMaze>,
+    ghosts: Res<GhostSpawner>,
+    windows: Res<Windows>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    mut lines: ResMut<DebugLines>,
+) {
+    if !editor.enabled {
+        return;
+    }
+
+    for x in 0..WIDTH_TILES as isize {
+        for y in 0..HEIGHT_TILES as isize {
+            let location = GridLocation { x, y };
+            let (size, color) = match layout.get(&location) {
+                Ok(None) | Err(_) => continue,
+                Ok(Some(Tile::Wall)) => (0.5, maze.wall_color),
+                Ok(Some(Tile::Door)) => (0.5, Color::PINK),
+                Ok(Some(Tile::Dot)) => (0.1, Color::WHITE),
+                Ok(Some(Tile::Energizer)) => (0.3, Color::WHITE),
+                Ok(Some(Tile::PacMan)) => (0.4, Color::YELLOW),
+                Ok(Some(Tile::Ghost(spawn))) => {
+                    let color = ghosts
+                        .roster
+                        .spawning_at(spawn)
+                        .next()
+                        .map(GhostSpec::color);
+                    (0.4, color.unwrap_or(Color::WHITE))
+                }
+            };
+            draw_box(&mut lines, location, size, color);
+        }
+    }
+
+    for location in editor.problems.iter().filter_map(Problem::location) {
+        draw_box(&mut lines, location, 0.5, Color::RED);
+    }
+
+    if let Some(cursor) = cursor_location(&windows, &cameras) {
+        draw_box(&mut lines, cursor, 0.6, Color::GRAY);
+    }
+}
+
+/// Draws a square around a tile, with `size` being half the width in tiles
+fn draw_box(lines: &mut DebugLines, location: GridLocation, size: f32, color: Color) {
+    let center = GRID.to_vec2(location).extend(0.0);
+    let x = (Vec2::X * GRID.size * size).extend(0.0);
+    let y = (Vec2::Y * GRID.size * size).extend(0.0);
+    lines.line_colored(center - x - y, center + x - y, 0.0, color);
+    lines.line_colored(center + x - y, center + x + y, 0.0, color);
+    lines.line_colored(center + x + y, center - x + y, 0.0, color);
+    lines.line_colored(center - x + y, center - x - y, 0.0, color);
+}
+
+fn update_status_display(
+    editor: Res<Editor>,
+    ghosts: Res<GhostSpawner>,
+    mut query: Query<&mut TextSprites, With<EditorStatusDisplay>>,
+) {
+    if !editor.is_changed() {
+        return;
+    }
+
+    let status = if editor.enabled {
+        let brush = match editor.brush {
+            None => "ERASE".to_string(),
+            Some(Tile::Wall) => "WALL".to_string(),
+            Some(Tile::Door) => "DOOR".to_string(),
+            Some(Tile::Dot) => "DOT".to_string(),
+            Some(Tile::Energizer) => "POWER".to_string(),
+            Some(Tile::PacMan) => "PAC-MAN".to_string(),
+            Some(Tile::Ghost(spawn)) => match ghosts.roster.spawning_at(spawn).next() {
+                // Stick to characters the font has
+                Some(ghost) => ghost
+                    .name
+                    .chars()
+                    .filter(char::is_ascii_alphanumeric)
+                    .collect::<String>()
+                    .to_uppercase(),
+                None => spawn.to_string(),
+            },
+        };
+        let problem = editor
+            .problems
+            .first()
+            .map(|problem| problem.to_string().to_uppercase())
+            .unwrap_or_default();
+        format!("{brush:<8}{problem}")
+    } else {
+        String::new()
+    };
+
+    for mut text in &mut query {
+        text.string = status.clone();
+    }
+}
@@ -1,8 +1,8 @@
 use crate::actor::ghost::Ghost;
 use crate::actor::mode::{FrightenedMode, SetMode, TickMode};
-use crate::actor::player::Player;
+use crate::actor::player::{Player, PlayerNumber};
 use crate::grid::GridLocation;
-use crate::score::{Score, UpdateScore};
+use crate::score::{PlayerScores, Score, UpdateScore};
 use bevy::prelude::*;
 
 pub struct FoodPlugin;
@@ -34,33 +34,45 @@ pub struct Food {
 #[derive(Component)]
 pub struct Energizer;
 
-pub struct Eat(pub Entity);
+/// Something being eaten, and the Pac-Man who ate it, if it wasn't eaten some other way
+pub struct Eat(pub Entity, pub Option<Entity>);
 
 fn eat(
     foods: Query<(Entity, &GridLocation), With<Food>>,
-    player: Query<&GridLocation, With<Player>>,
+    player: Query<(Entity, &GridLocation), With<Player>>,
     mut eat_events: EventWriter<Eat>,
 ) {
-    for player_location in &player {
+    for (player, player_location) in &player {
         for (food_entity, food_location) in &foods {
             if player_location == food_location {
-                eat_events.send(Eat(food_entity));
+                eat_events.send(Eat(food_entity, Some(player)));
             }
         }
     }
 }
 
 fn destroy(mut commands: Commands, mut eat_events: EventReader<Eat>, ghosts: Query<&Ghost>) {
-    for Eat(food) in eat_events.iter() {
+    for Eat(food, _) in eat_events.iter() {
         if !ghosts.contains(*food) {
             commands.entity(*food).despawn();
         }
     }
 }
 
-fn add_score(mut eat_events: EventReader<Eat>, mut score: ResMut<Score>, query: Query<&Food>) {
-    for Eat(food) in eat_events.iter() {
-        *score += query.get(*food).expect("Eaten thing is not Food").points;
+fn add_score(
+    mut eat_events: EventReader<Eat>,
+    mut score: ResMut<Score>,
+    mut player_scores: ResMut<PlayerScores>,
+    query: Query<&Food>,
+    players: Query<&PlayerNumber>,
+) {
+    for Eat(food, eater) in eat_events.iter() {
+        let points = query.get(*food).expect("Eaten thing is not Food").points;
+        *score += points;
+
+        if let Some(player) = eater.and_then(|eater| players.get(eater).ok()) {
+            player_scores.add(**player, points);
+        }
     }
 }
 
@@ -69,7 +81,7 @@ fn eat_energizer(
     energizers: Query<&Energizer>,
     mut mode: ResMut<FrightenedMode>,
 ) {
-    for Eat(eaten) in eat_events.iter() {
+    for Eat(eaten, _) in eat_events.iter() {
         if energizers.contains(*eaten) {
             *mode = FrightenedMode::Enabled;
         }
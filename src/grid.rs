@@ -1,6 +1,8 @@
 use crate::actor::movement::Dir;
+use crate::layout::ActorKind;
 use bevy::prelude::*;
 use bevy_inspector_egui::Inspectable;
+use serde::{Deserialize, Serialize};
 use std::ops::Mul;
 
 pub struct GridPlugin;
@@ -11,7 +13,8 @@ impl Plugin for GridPlugin {
             update_grid_location_from_transform
                 .after(SetTransform)
                 .label(SetGridLocation),
-        );
+        )
+        .add_system_to_stage(CoreStage::Last, mark_arrivals);
     }
 }
 
@@ -112,7 +115,9 @@ impl Mul<f32> for Grid {
     }
 }
 
-#[derive(Component, Debug, Copy, Clone, Default, Eq, PartialEq, Inspectable)]
+#[derive(
+    Component, Debug, Copy, Clone, Default, Eq, PartialEq, Hash, Inspectable, Serialize, Deserialize,
+)]
 pub struct GridLocation {
     pub x: isize,
     pub y: isize,
@@ -145,6 +150,13 @@ impl GridLocation {
 #[component(storage = "SparseSet")]
 pub struct MovingTo(pub Vec3);
 
+/// An actor that reached a new tile during the last frame.
+///
+/// This is a component rather than change detection so it can be saved and restored with the rest
+/// of the game, e.g. when netplay rolls back.
+#[derive(Component, Copy, Clone, Default)]
+pub struct Arrived;
+
 // Speed in pixels per second
 #[derive(Component, Default, Deref)]
 pub struct Speed(pub f32);
@@ -167,3 +179,20 @@ fn update_grid_location_from_transform(
         }
     }
 }
+
+fn mark_arrivals(
+    mut commands: Commands,
+    query: Query<(Entity, ChangeTrackers<GridLocation>, Option<&Arrived>), With<ActorKind>>,
+) {
+    for (entity, location, arrived) in &query {
+        match (location.is_changed(), arrived.is_some()) {
+            (true, false) => {
+                commands.entity(entity).insert(Arrived);
+            }
+            (false, true) => {
+                commands.entity(entity).remove::<Arrived>();
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,374 @@
+//! A reinforcement learning environment in the style of OpenAI Gym, running the real game rules
+//! without a window.
+//!
+//! Use [`PacManEnv`] from Rust, or run `pac-man gym` and talk to it with one JSON object per line:
+//!
+//! ```text
+//! > {"command": "reset", "seed": 7}
+//! < {"tiles": [...], "player": {...}, "ghosts": [...], "mode": "scatter", ...}
+//! > {"command": "step", "action": "left"}
+//! < {"observation": {...}, "reward": 10.0, "done": false, "info": {...}}
+//! ```
+//!
+//! Actions are `"up"`, `"down"`, `"left"`, `"right"` or `null` to carry on as before.
+
+use std::collections::HashSet;
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+use bevy::asset::AssetPlugin;
+use bevy::core::{CorePlugin, TaskPoolOptions};
+use bevy::ecs::event::ManualEventReader;
+use bevy::input::InputPlugin;
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use bevy::utils::Instant;
+use serde::{Deserialize, Serialize};
+
+use crate::actor::autopilot::Autopilot;
+use crate::actor::ghost::{Frightened, Ghost, Personality, Respawning};
+use crate::actor::mode::{FrightenedMode, Mode};
+use crate::actor::movement::Dir;
+use crate::actor::player::{Lives, Player, PlayerDied, SyncedInput};
+use crate::food::Food;
+use crate::grid::GridLocation;
+use crate::layout::{Layout, Tile};
+use crate::level::{CurrentLevel, HEIGHT_TILES, WIDTH_TILES};
+use crate::players::PacMen;
+use crate::random::GameRng;
+use crate::score::Score;
+use crate::GamePlugins;
+
+/// How long each frame of the game lasts
+const FRAME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// A step ends early if Pac-Man hasn't reached a new tile after this many frames,
+/// e.g. because he's stopped against a wall
+const MAX_FRAMES_PER_STEP: u32 = 60;
+
+/// An action to take, or `None` to keep going
+pub type Action = Option<Dir>;
+
+/// The game rules without a window or anything else to draw them with
+pub(crate) fn headless_app() -> App {
+    let mut app = App::new();
+
+    // One thread, so systems that could run in either order always run in the same one,
+    // and a seed always plays out the same way
+    app.add_plugins(MinimalPlugins.set(CorePlugin {
+        task_pool_options: TaskPoolOptions::with_num_threads(1),
+    }))
+    .add_plugin(AssetPlugin::default())
+    .add_plugin(InputPlugin)
+    .add_asset::<Image>()
+    .add_asset::<TextureAtlas>()
+    .add_plugins(GamePlugins);
+
+    app
+}
+
+pub struct PacManEnv {
+    app: App,
+    now: Instant,
+    deaths: ManualEventReader<PlayerDied>,
+}
+
+impl PacManEnv {
+    pub fn new(seed: u64) -> Self {
+        let mut app = headless_app();
+
+        app.insert_resource(GameRng::seeded(seed))
+            // The agent is the only one steering
+            .insert_resource(Autopilot::Off)
+            .init_resource::<SyncedInput>();
+
+        let now = app.world.resource::<Time>().startup();
+        let deaths = app.world.resource::<Events<PlayerDied>>().get_reader();
+
+        let mut env = Self { app, now, deaths };
+        // Spawn the level
+        env.update();
+        env
+    }
+
+    /// Lets one of the bots play instead of the agent, which should then step without an action
+    pub fn set_autopilot(&mut self, autopilot: Autopilot) {
+        self.app.insert_resource(autopilot);
+    }
+
+    /// Starts a new game
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        *self = Self::new(seed);
+        self.observe()
+    }
+
+    /// Steers Pac-Man, then plays until he reaches the next tile
+    pub fn step(&mut self, action: Action) -> Step {
+        // The agent holds its last direction until it picks another one
+        if let Some(dir) = action {
+            let pac_men = **self.app.world.resource::<PacMen>();
+            self.app
+                .insert_resource(SyncedInput(vec![Some(dir); pac_men]));
+        }
+
+        let start = self.info();
+        let mut frames = 0;
+        let mut frightened_frames = 0;
+        let mut killed_by = None;
+
+        while frames < MAX_FRAMES_PER_STEP {
+            self.update();
+            frames += 1;
+
+            if *self.app.world.resource::<FrightenedMode>() == FrightenedMode::Enabled {
+                frightened_frames += 1;
+            }
+            killed_by = killed_by.or_else(|| self.killer());
+
+            let info = self.info();
+            if info.lives != start.lives
+                || info.level != start.level
+                || self.player_location() != start.location
+            {
+                break;
+            }
+        }
+
+        let info = self.info();
+
+        Step {
+            observation: self.observe(),
+            // The score can go down when players take turns
+            reward: info.score as f32 - start.score as f32,
+            done: info.lives == 0,
+            info: StepInfo {
+                score: info.score,
+                lives: info.lives,
+                level: info.level,
+                died: info.lives < start.lives,
+                cleared: info.level > start.level,
+                killed_by,
+                frames,
+                frightened_frames,
+            },
+        }
+    }
+
+    pub fn observe(&mut self) -> Observation {
+        let world = &mut self.app.world;
+
+        let food: HashSet<GridLocation> = world
+            .query_filtered::<&GridLocation, (With<Food>, Without<Ghost>)>()
+            .iter(world)
+            .copied()
+            .collect();
+
+        let layout = world.resource::<Layout>();
+        let tiles = (0..HEIGHT_TILES as isize)
+            .rev()
+            .map(|y| {
+                (0..WIDTH_TILES as isize)
+                    .map(|x| {
+                        let loc = GridLocation { x, y };
+                        match layout.get(&loc) {
+                            Ok(Some(tile @ (Tile::Wall | Tile::Door))) => tile.to_char(),
+                            Ok(Some(tile @ (Tile::Dot | Tile::Energizer)))
+                                if food.contains(&loc) =>
+                            {
+                                tile.to_char()
+                            }
+                            _ => ' ',
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut player = world.query_filtered::<(&GridLocation, &Dir), With<Player>>();
+        let player = player
+            .iter(world)
+            .next()
+            .map(|(location, dir)| ActorObservation {
+                location: *location,
+                dir: *dir,
+            });
+
+        let mut ghosts = world.query::<(
+            &Ghost,
+            &Name,
+            &GridLocation,
+            &Dir,
+            Option<&Frightened>,
+            Option<&Respawning>,
+        )>();
+        let ghosts = ghosts
+            .iter(world)
+            .map(
+                |(ghost, name, location, dir, frightened, respawning)| GhostObservation {
+                    name: name.to_string(),
+                    personality: ghost.personality,
+                    actor: ActorObservation {
+                        location: *location,
+                        dir: *dir,
+                    },
+                    frightened: frightened.is_some(),
+                    respawning: respawning.is_some(),
+                },
+            )
+            .collect();
+
+        Observation {
+            tiles,
+            player,
+            ghosts,
+            mode: *world.resource::<Mode>(),
+            frightened: *world.resource::<FrightenedMode>() == FrightenedMode::Enabled,
+            dots_remaining: food.len(),
+        }
+    }
+
+    fn update(&mut self) {
+        self.now += FRAME;
+        self.app
+            .insert_resource(TimeUpdateStrategy::ManualInstant(self.now));
+        self.app.update();
+    }
+
+    /// The personality of the ghost that caught Pac-Man in the last frame, if one did
+    fn killer(&mut self) -> Option<Personality> {
+        let world = &self.app.world;
+        let events = world.resource::<Events<PlayerDied>>();
+
+        self.deaths
+            .iter(events)
+            .filter_map(|died| died.ghost)
+            .filter_map(|ghost| world.get::<Ghost>(ghost))
+            .map(|ghost| ghost.personality)
+            .last()
+    }
+
+    fn player_location(&mut self) -> Option<GridLocation> {
+        let world = &mut self.app.world;
+        let mut players = world.query_filtered::<&GridLocation, With<Player>>();
+        let location = players.iter(world).next().copied();
+        location
+    }
+
+    fn info(&mut self) -> StepStart {
+        StepStart {
+            location: self.player_location(),
+            score: **self.app.world.resource::<Score>(),
+            lives: **self.app.world.resource::<Lives>(),
+            level: **self.app.world.resource::<CurrentLevel>(),
+        }
+    }
+}
+
+/// What was going on at the start of a step, to compare with the end
+struct StepStart {
+    location: Option<GridLocation>,
+    score: u32,
+    lives: usize,
+    level: usize,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Observation {
+    /// The maze with the top row first, drawn like a maze file but only showing walls, doors and
+    /// the food that hasn't been eaten yet
+    pub tiles: Vec<String>,
+    /// Missing while the level is being set up
+    pub player: Option<ActorObservation>,
+    pub ghosts: Vec<GhostObservation>,
+    pub mode: Mode,
+    pub frightened: bool,
+    pub dots_remaining: usize,
+}
+
+#[derive(Serialize, Copy, Clone, Debug)]
+pub struct ActorObservation {
+    pub location: GridLocation,
+    pub dir: Dir,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct GhostObservation {
+    pub name: String,
+    pub personality: Personality,
+    #[serde(flatten)]
+    pub actor: ActorObservation,
+    pub frightened: bool,
+    /// Eaten, and on the way home as a pair of eyes
+    pub respawning: bool,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Step {
+    pub observation: Observation,
+    /// Points scored during the step
+    pub reward: f32,
+    /// Whether Pac-Man is out of lives
+    pub done: bool,
+    pub info: StepInfo,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct StepInfo {
+    pub score: u32,
+    pub lives: usize,
+    pub level: usize,
+    pub died: bool,
+    /// Whether Pac-Man ate the last dot and moved on to the next level
+    pub cleared: bool,
+    /// The kind of ghost that caught Pac-Man, if one did
+    pub killed_by: Option<Personality>,
+    /// How many frames of the game the step took
+    pub frames: u32,
+    /// How many of those frames the ghosts were frightened for
+    pub frightened_frames: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "command", rename_all = "lowercase")]
+enum Request {
+    Reset {
+        #[serde(default)]
+        seed: u64,
+    },
+    Step {
+        action: Action,
+    },
+}
+
+/// Answers requests, one per line, until the input runs out
+pub fn serve(input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    let mut env: Option<PacManEnv> = None;
+
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str(&line) {
+            Ok(Request::Reset { seed }) => {
+                let env = env.insert(PacManEnv::new(seed));
+                serde_json::to_string(&env.observe())
+            }
+            Ok(Request::Step { action }) => match &mut env {
+                Some(env) => serde_json::to_string(&env.step(action)),
+                None => Ok(error_response("reset before the first step")),
+            },
+            Err(err) => Ok(error_response(&err.to_string())),
+        }?;
+
+        writeln!(output, "{response}")?;
+        output.flush()?;
+    }
+
+    Ok(())
+}
+
+fn error_response(message: &str) -> String {
+    serde_json::json!({ "error": message }).to_string()
+}
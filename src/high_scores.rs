@@ -0,0 +1,528 @@
+//! The top ten scores, kept in a file between games. When the last life is lost, everyone whose
+//! score made the table enters their initials, and then the table is shown until someone presses
+//! start for a new game.
+//!
+//! The table is saved to `pac-man/high-scores.json` in the user's data directory, or wherever
+//! `HIGH_SCORES_FILE` says.
+
+use std::cmp::Reverse;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::actor::movement::Dir;
+use crate::actor::player::{Lives, PlayerDied, UpdateLives};
+use crate::controls::{Action, Controls};
+use crate::grid::{GridBundle, GridLocation, Layer};
+use crate::layout::Layout;
+use crate::level::{CurrentLevel, RestartLevel, UnloadLevel, GRID, WIDTH_TILES};
+use crate::maze::{CurrentMaze, MazePlaylist};
+use crate::players::{PacMen, Players, SwitchPlayers};
+use crate::score::{HighScore, PlayerScores, Score};
+use crate::text::{SetTextSprites, TextBundle, TextSprites};
+
+/// How many scores the table keeps
+const TABLE_SIZE: usize = 10;
+
+const INITIALS: usize = 3;
+
+/// How long the letter being chosen flashes on and off for
+const BLINK_TIME: f32 = 0.25;
+
+/// The title, a line for each score and a line at the bottom
+const ROWS: usize = TABLE_SIZE + 2;
+
+pub struct HighScoresPlugin;
+
+impl Plugin for HighScoresPlugin {
+    fn build(&self, app: &mut App) {
+        let table = match get_high_scores_file() {
+            Some(path) => HighScoreTable::load(&path).unwrap_or_else(|err| {
+                warn!("Couldn't read high scores from {}: {err}", path.display());
+                HighScoreTable::default()
+            }),
+            None => HighScoreTable::default(),
+        };
+
+        app.insert_resource(table)
+            .init_resource::<Screen>()
+            .add_startup_system(setup_high_score_text)
+            .add_startup_system(show_best_score)
+            .add_system(
+                end_game
+                    .label(EndGame)
+                    .after(SwitchPlayers)
+                    .after(UpdateLives),
+            )
+            .add_system(start_new_game.after(EndGame))
+            // After starting a new game, so the start that confirms the last letter doesn't also
+            // skip the table
+            .add_system(enter_initials.after(start_new_game))
+            .add_system(
+                update_high_score_text
+                    .after(enter_initials)
+                    .before(SetTextSprites),
+            );
+    }
+}
+
+#[derive(SystemLabel)]
+struct EndGame;
+
+/// The best scores, best first
+#[derive(Resource, Default, Clone, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct HighScoreTable(Vec<HighScoreEntry>);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HighScoreEntry {
+    pub initials: String,
+    pub score: u32,
+}
+
+impl HighScoreTable {
+    /// Reads the table from a file, which is empty if there isn't one yet
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let file = match std::fs::read_to_string(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err),
+        };
+        let mut table: Self = serde_json::from_str(&file)?;
+        table.0.sort_by_key(|entry| Reverse(entry.score));
+        table.0.truncate(TABLE_SIZE);
+        Ok(table)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn entries(&self) -> &[HighScoreEntry] {
+        &self.0
+    }
+
+    pub fn best(&self) -> u32 {
+        self.0.first().map_or(0, |entry| entry.score)
+    }
+
+    /// Whether a score is good enough to go in the table
+    pub fn qualifies(&self, score: u32) -> bool {
+        score > 0
+            && match self.0.get(TABLE_SIZE - 1) {
+                Some(last) => score > last.score,
+                None => true,
+            }
+    }
+
+    /// Adds a score below any that are as good, dropping the worst if the table is full
+    pub fn insert(&mut self, initials: String, score: u32) {
+        let position = self.0.partition_point(|entry| entry.score >= score);
+        self.0.insert(position, HighScoreEntry { initials, score });
+        self.0.truncate(TABLE_SIZE);
+    }
+}
+
+/// What's shown instead of the game, once it's over
+#[derive(Resource, Default)]
+enum Screen {
+    #[default]
+    Playing,
+    /// Everyone whose score made the table, in turn
+    EnteringInitials(Vec<NewEntry>),
+    /// Showing the table until someone presses start
+    Attract,
+}
+
+struct NewEntry {
+    /// Which player this is, starting from 0
+    player: usize,
+    /// Whose controls choose the letters, when more than one Pac-Man was on the board
+    controls: usize,
+    score: u32,
+    /// The letters chosen so far
+    initials: String,
+    /// The letter being chosen
+    letter: char,
+}
+
+impl NewEntry {
+    fn new(player: usize, controls: usize, score: u32) -> Self {
+        Self {
+            player,
+            controls,
+            score,
+            initials: String::new(),
+            letter: 'A',
+        }
+    }
+}
+
+/// A line of the high score screen, counting down from the top
+#[derive(Component)]
+struct HighScoreText(usize);
+
+fn setup_high_score_text(mut commands: Commands) {
+    commands
+        .spawn((SpatialBundle::default(), Name::new("High Scores")))
+        .with_children(|builder| {
+            for row in 0..ROWS {
+                // A gap under the title and above the bottom line
+                let y = match row {
+                    0 => 27,
+                    _ if row == ROWS - 1 => 3,
+                    _ => 26 - 2 * row as isize,
+                };
+
+                builder.spawn(TextBundle::default()).insert((
+                    GridBundle::new(GRID, GridLocation { x: 0, y }, Layer::UI),
+                    Name::new("High Score Text"),
+                    HighScoreText(row),
+                ));
+            }
+        });
+}
+
+fn show_best_score(table: Res<HighScoreTable>, mut high_score: ResMut<HighScore>) {
+    *high_score = HighScore::new(table.best());
+}
+
+/// The game is over when the last player still playing loses their last life
+#[allow(clippy::too_many_arguments)]
+fn end_game(
+    mut died_events: EventReader<PlayerDied>,
+    mut unload_events: EventWriter<UnloadLevel>,
+    mut screen: ResMut<Screen>,
+    lives: Res<Lives>,
+    score: Res<Score>,
+    player_scores: Res<PlayerScores>,
+    players: Res<Players>,
+    pac_men: Res<PacMen>,
+    table: Res<HighScoreTable>,
+) {
+    if died_events.iter().count() == 0 || **lives > 0 || !matches!(*screen, Screen::Playing) {
+        return;
+    }
+
+    info!("Game over");
+    unload_events.send(UnloadLevel);
+
+    let mut entries: Vec<NewEntry> = if **pac_men > 1 {
+        (0..**pac_men)
+            .map(|player| NewEntry::new(player, player, player_scores.get(player)))
+            .collect()
+    } else {
+        (0..players.count())
+            .map(|player| NewEntry::new(player, 0, players.score(player, &score)))
+            .collect()
+    };
+    entries.retain(|entry| table.qualifies(entry.score));
+
+    *screen = match entries.is_empty() {
+        true => Screen::Attract,
+        false => Screen::EnteringInitials(entries),
+    };
+}
+
+/// Up and down choose a letter, right or start moves on to the next one and left goes back
+fn enter_initials(
+    controls: Res<Controls>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut screen: ResMut<Screen>,
+    mut table: ResMut<HighScoreTable>,
+) {
+    let entries = match &mut *screen {
+        Screen::EnteringInitials(entries) => entries,
+        _ => return,
+    };
+    let entry = match entries.first_mut() {
+        Some(entry) => entry,
+        None => return,
+    };
+
+    let pressed = |dir: Dir| {
+        let action = Action::steer(entry.controls, dir).or_else(|| Action::steer(0, dir));
+        matches!(action, Some(action) if controls.just_pressed(&keyboard_input, action))
+    };
+
+    if pressed(Dir::Up) {
+        entry.letter = next_letter(entry.letter, 1);
+    } else if pressed(Dir::Down) {
+        entry.letter = next_letter(entry.letter, -1);
+    } else if pressed(Dir::Left) {
+        if let Some(letter) = entry.initials.pop() {
+            entry.letter = letter;
+        }
+    } else if pressed(Dir::Right) || controls.just_pressed(&keyboard_input, Action::Start) {
+        entry.initials.push(entry.letter);
+    }
+
+    if entry.initials.len() < INITIALS {
+        return;
+    }
+
+    let entry = entries.remove(0);
+    info!("{} scored {}", entry.initials, entry.score);
+    table.insert(entry.initials, entry.score);
+
+    if let Some(path) = get_high_scores_file() {
+        if let Err(err) = table.save(&path) {
+            error!("Couldn't save high scores to {}: {err}", path.display());
+        }
+    }
+
+    if entries.is_empty() {
+        *screen = Screen::Attract;
+    }
+}
+
+/// Starts again from the first level with everyone's scores and lives reset
+#[allow(clippy::too_many_arguments)]
+fn start_new_game(
+    controls: Res<Controls>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut screen: ResMut<Screen>,
+    mut restart_events: EventWriter<RestartLevel>,
+    mut score: ResMut<Score>,
+    mut player_scores: ResMut<PlayerScores>,
+    mut lives: ResMut<Lives>,
+    mut players: ResMut<Players>,
+    mut level: ResMut<CurrentLevel>,
+    mut layout: ResMut<Layout>,
+    mut current_maze: ResMut<CurrentMaze>,
+    playlist: Res<MazePlaylist>,
+) {
+    if !matches!(*screen, Screen::Attract) || !controls.just_pressed(&keyboard_input, Action::Start)
+    {
+        return;
+    }
+
+    *score = Score::default();
+    *player_scores = PlayerScores::default();
+    *lives = Lives::default();
+    *players = Players::new(players.count());
+    *level = CurrentLevel::default();
+
+    let maze = playlist.maze_for_level(**level);
+    info!("Starting a new game on {}", maze.name);
+    *layout = maze.layout.clone();
+    *current_maze = CurrentMaze(maze);
+    restart_events.send(RestartLevel);
+    *screen = Screen::Playing;
+}
+
+fn update_high_score_text(
+    time: Res<Time>,
+    screen: Res<Screen>,
+    table: Res<HighScoreTable>,
+    mut blink: Local<Timer>,
+    mut query: Query<(&mut TextSprites, &HighScoreText)>,
+) {
+    if blink.duration().is_zero() {
+        *blink = Timer::from_seconds(BLINK_TIME * 2.0, TimerMode::Repeating);
+    }
+    blink.tick(time.delta());
+    let letter_shown = blink.percent() < 0.5;
+
+    let mut lines = vec![String::new(); ROWS];
+    match &*screen {
+        Screen::Playing => {}
+        Screen::EnteringInitials(entries) => {
+            if let Some(entry) = entries.first() {
+                lines[0] = "ENTER YOUR INITIALS".to_string();
+
+                let mut initials = entry.initials.clone();
+                initials.push(if letter_shown { entry.letter } else { ' ' });
+                let initials = format!("{initials:-<INITIALS$}");
+                let player = format!("{}UP", entry.player + 1);
+                lines[2] = table_line(&player, entry.score, &initials);
+            }
+            lines[ROWS - 1] = "GAME OVER".to_string();
+        }
+        Screen::Attract => {
+            lines[0] = "HIGH SCORES".to_string();
+            for (rank, entry) in table.entries().iter().enumerate() {
+                lines[rank + 1] = table_line(&ordinal(rank + 1), entry.score, &entry.initials);
+            }
+            lines[ROWS - 1] = "PUSH START BUTTON".to_string();
+        }
+    }
+
+    for (mut text, row) in &mut query {
+        let line = format!("{:^WIDTH_TILES$}", lines[row.0]);
+        // Only when it's different, so the letters aren't respawned every frame
+        if text.string != line {
+            text.string = line;
+        }
+    }
+}
+
+/// A line of the table, lined up with the others
+fn table_line(label: &str, score: u32, initials: &str) -> String {
+    format!("{label:<4} {score:>7}  {initials:<INITIALS$}")
+}
+
+/// `1ST`, `2ND`, and so on
+fn ordinal(rank: usize) -> String {
+    let suffix = match (rank % 10, rank % 100) {
+        (_, 11..=13) => "TH",
+        (1, _) => "ST",
+        (2, _) => "ND",
+        (3, _) => "RD",
+        _ => "TH",
+    };
+    format!("{rank}{suffix}")
+}
+
+/// The letter `offset` letters on, wrapping around from Z to A
+fn next_letter(letter: char, offset: i8) -> char {
+    let index = (letter as u8 - b'A') as i8 + offset;
+    (b'A' + index.rem_euclid(26) as u8) as char
+}
+
+/// Where the table is kept, e.g. `HIGH_SCORES_FILE=scores.json`
+fn get_high_scores_file() -> Option<PathBuf> {
+    match std::env::var_os("HIGH_SCORES_FILE") {
+        Some(path) => Some(PathBuf::from(path)),
+        None => data_dir().map(|dir| dir.join("pac-man").join("high-scores.json")),
+    }
+}
+
+/// Where each platform keeps data belonging to the user
+fn data_dir() -> Option<PathBuf> {
+    let home = || std::env::var_os("HOME").map(PathBuf::from);
+
+    if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home().map(|home| home.join("Library").join("Application Support"))
+    } else {
+        std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| home().map(|home| home.join(".local").join("share")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(scores: &[u32]) -> HighScoreTable {
+        let mut table = HighScoreTable::default();
+        for (i, score) in scores.iter().enumerate() {
+            table.insert(format!("P{i}"), *score);
+        }
+        table
+    }
+
+    fn scores(table: &HighScoreTable) -> Vec<u32> {
+        table.entries().iter().map(|entry| entry.score).collect()
+    }
+
+    fn initials(table: &HighScoreTable) -> Vec<&str> {
+        table
+            .entries()
+            .iter()
+            .map(|entry| entry.initials.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn any_score_qualifies_until_the_table_is_full() {
+        let table = table(&[500, 100]);
+
+        assert!(table.qualifies(10));
+        assert!(!table.qualifies(0));
+    }
+
+    #[test]
+    fn a_full_table_needs_a_better_score_than_the_last() {
+        let table = table(&[100; TABLE_SIZE]);
+
+        assert!(!table.qualifies(100));
+        assert!(table.qualifies(101));
+    }
+
+    #[test]
+    fn inserts_in_order() {
+        let table = table(&[200, 500, 300]);
+
+        assert_eq!(scores(&table), [500, 300, 200]);
+        assert_eq!(table.best(), 500);
+    }
+
+    #[test]
+    fn ties_go_below_the_earlier_score() {
+        let mut table = table(&[300, 200]);
+        table.insert("NEW".to_string(), 300);
+
+        assert_eq!(initials(&table), ["P0", "NEW", "P1"]);
+    }
+
+    #[test]
+    fn drops_the_worst_score_when_full() {
+        let mut table = table(&[100; TABLE_SIZE]);
+        table.insert("NEW".to_string(), 150);
+
+        assert_eq!(table.entries().len(), TABLE_SIZE);
+        assert_eq!(initials(&table)[0], "NEW");
+        assert_eq!(initials(&table)[TABLE_SIZE - 1], "P8");
+    }
+
+    #[test]
+    fn loads_a_missing_file_as_empty() {
+        let path = std::env::temp_dir().join("pac-man-test-missing-high-scores.json");
+
+        let table = HighScoreTable::load(&path).unwrap();
+
+        assert!(table.entries().is_empty());
+    }
+
+    #[test]
+    fn sorts_and_trims_a_loaded_file() {
+        let path = std::env::temp_dir().join(format!(
+            "pac-man-test-high-scores-{}.json",
+            std::process::id()
+        ));
+        let entries: Vec<String> = (1..=TABLE_SIZE + 2)
+            .map(|score| format!(r#"{{"initials": "AAA", "score": {score}}}"#))
+            .collect();
+        std::fs::write(&path, format!("[{}]", entries.join(","))).unwrap();
+
+        let table = HighScoreTable::load(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let table = table.unwrap();
+        assert_eq!(table.entries().len(), TABLE_SIZE);
+        assert_eq!(table.best(), TABLE_SIZE as u32 + 2);
+        assert_eq!(scores(&table).last(), Some(&3));
+    }
+
+    #[test]
+    fn ordinals() {
+        let ranks = [1, 2, 3, 4, 10, 11, 12, 13, 21, 22, 23, 111, 112];
+        let ordinals: Vec<String> = ranks.into_iter().map(ordinal).collect();
+
+        assert_eq!(
+            ordinals,
+            [
+                "1ST", "2ND", "3RD", "4TH", "10TH", "11TH", "12TH", "13TH", "21ST", "22ND", "23RD",
+                "111TH", "112TH"
+            ]
+        );
+    }
+
+    #[test]
+    fn letters_wrap_around() {
+        assert_eq!(next_letter('A', 1), 'B');
+        assert_eq!(next_letter('Z', 1), 'A');
+        assert_eq!(next_letter('A', -1), 'Z');
+        assert_eq!(next_letter('M', 0), 'M');
+    }
+}
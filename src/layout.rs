@@ -1,27 +1,39 @@
+use std::fmt;
+use std::str::FromStr;
+
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    actor::movement::Dir,
+    from_env::FromEnv,
     grid::GridLocation,
     level::{HEIGHT_TILES, WIDTH_TILES},
+    maze::{CurrentMaze, MazePlaylist},
 };
 
 pub struct LayoutPlugin;
 
 impl Plugin for LayoutPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(Layout::load());
+        let playlist = MazePlaylist::from_env();
+        let maze = playlist.maze_for_level(1);
+        app.insert_resource(maze.layout.clone())
+            .insert_resource(CurrentMaze(maze))
+            .insert_resource(playlist);
     }
 }
 
-#[derive(Resource)]
-pub struct Layout([[Option<Tile>; WIDTH_TILES]; HEIGHT_TILES]);
+#[derive(Resource, Clone)]
+pub struct Layout {
+    tiles: [[Option<Tile>; WIDTH_TILES]; HEIGHT_TILES],
+    pub portals: Vec<Portal>,
+}
 
 impl Layout {
-    fn load() -> Layout {
+    pub fn from_bmp(level_bmp: &[u8]) -> Layout {
         let mut tiles = [[None; WIDTH_TILES]; HEIGHT_TILES];
 
-        let level_bmp = include_bytes!("../assets/level.bmp");
-
         // Find start of image data
         const OFFSET: usize = 0x0A;
         let offset = u32::from_le_bytes(level_bmp[OFFSET..OFFSET + 4].try_into().unwrap()) as usize;
@@ -36,36 +48,423 @@ impl Layout {
                     0x04 => Some(Tile::Energizer),
                     0x05 => Some(Tile::PacMan),
                     0x06 => Some(Tile::Door),
-                    0x07 => Some(Tile::Blinky),
-                    0x08 => Some(Tile::Pinky),
-                    0x09 => Some(Tile::Inky),
-                    0x0A => Some(Tile::Clyde),
+                    0x07 => Some(Tile::Ghost('B')),
+                    0x08 => Some(Tile::Ghost('P')),
+                    0x09 => Some(Tile::Ghost('I')),
+                    0x0A => Some(Tile::Ghost('C')),
                     _ => None,
                 };
             }
         }
 
-        Layout(tiles)
+        Layout {
+            tiles,
+            portals: vec![],
+        }
+    }
+
+    /// The tile at a location, which is `None` if it's empty
+    pub fn get(&self, loc: &GridLocation) -> Result<Option<Tile>, OutOfBounds> {
+        if !self.in_bounds(loc) {
+            return Err(OutOfBounds(*loc));
+        }
+        Ok(self.tiles[loc.y as usize][loc.x as usize])
+    }
+
+    /// Panics if the location is off the layout
+    pub fn set(&mut self, loc: &GridLocation, tile: Option<Tile>) {
+        assert!(self.in_bounds(loc), "{loc:?} is off the layout");
+        self.tiles[loc.y as usize][loc.x as usize] = tile;
     }
 
-    pub fn get(&self, loc: &GridLocation) -> Option<Tile> {
-        self.0[loc.y as usize][(loc.x.max(0) as usize).min(WIDTH_TILES - 1)]
+    pub fn in_bounds(&self, loc: &GridLocation) -> bool {
+        (0..WIDTH_TILES as isize).contains(&loc.x) && (0..HEIGHT_TILES as isize).contains(&loc.y)
     }
 
+    /// Whether a location off the layout is part of a tunnel, between the edge and a portal
+    pub fn in_tunnel(&self, loc: &GridLocation) -> bool {
+        let between = |a: isize, b: isize, x: isize| (a.min(b)..=a.max(b)).contains(&x);
+
+        !self.in_bounds(loc)
+            && self
+                .portals
+                .iter()
+                .flat_map(|portal| [portal.0, portal.1])
+                .any(|portal| {
+                    let edge = GridLocation {
+                        x: portal.x.clamp(0, WIDTH_TILES as isize - 1),
+                        y: portal.y.clamp(0, HEIGHT_TILES as isize - 1),
+                    };
+                    between(edge.x, portal.x, loc.x) && between(edge.y, portal.y, loc.y)
+                })
+    }
+
+    /// Whether nothing can go here. Off the layout, only tunnels are open.
     pub fn collides(&self, loc: &GridLocation) -> bool {
-        self.get(loc) == Some(Tile::Wall)
+        if self.in_bounds(loc) {
+            self.get(loc) == Ok(Some(Tile::Wall))
+        } else {
+            !self.in_tunnel(loc)
+        }
+    }
+
+    pub fn is_walkable_for(&self, loc: &GridLocation, kind: ActorKind) -> bool {
+        if self.collides(loc) {
+            return false;
+        }
+
+        // Only ghosts going in or out of the house can use its door
+        self.get(loc) != Ok(Some(Tile::Door)) || kind == ActorKind::HouseGhost
+    }
+
+    /// The locations one step away in each direction, going through portals.
+    /// Directions are in the order ghosts prefer them when breaking ties: up, left, down, right.
+    pub fn neighbours(&self, loc: GridLocation) -> impl Iterator<Item = (Dir, GridLocation)> + '_ {
+        [Dir::Up, Dir::Left, Dir::Down, Dir::Right]
+            .into_iter()
+            .map(move |dir| (dir, self.shift(loc, dir)))
+    }
+
+    /// Where an actor at `loc` moving in `dir` really is, if it's just stepped into a portal
+    pub fn through_portal(&self, loc: GridLocation, dir: Dir) -> Option<GridLocation> {
+        self.portals.iter().find_map(|portal| portal.exit(loc, dir))
+    }
+
+    /// The location one step from `loc` in `dir`, going through any portals on the way
+    pub fn shift(&self, loc: GridLocation, dir: Dir) -> GridLocation {
+        let loc = self.through_portal(loc, dir).unwrap_or(loc).shift(dir);
+        self.through_portal(loc, dir).unwrap_or(loc)
+    }
+
+    /// Straight-line distance between two locations, taking a shortcut through a portal if there
+    /// is one
+    pub fn wrapped_distance(&self, from: GridLocation, to: GridLocation) -> f32 {
+        let distance =
+            |a: GridLocation, b: GridLocation| a.to_unscaled_vec2().distance(b.to_unscaled_vec2());
+
+        self.portals
+            .iter()
+            .flat_map(|&Portal(a, b)| [(a, b), (b, a)])
+            .map(|(entrance, exit)| distance(from, entrance) + distance(exit, to))
+            .fold(distance(from, to), f32::min)
+    }
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout {
+            tiles: [[None; WIDTH_TILES]; HEIGHT_TILES],
+            portals: vec![],
+        }
+    }
+}
+
+/// Parses a layout drawn as text, one row per line with the top row first.
+/// Short rows are padded with empty tiles.
+impl FromStr for Layout {
+    type Err = ParseLayoutError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rows: Vec<&str> = s.lines().collect();
+
+        if rows.len() != HEIGHT_TILES {
+            return Err(ParseLayoutError::WrongHeight(rows.len()));
+        }
+
+        let mut tiles = [[None; WIDTH_TILES]; HEIGHT_TILES];
+
+        for (row, line) in tiles.iter_mut().rev().zip(rows) {
+            if line.chars().count() > WIDTH_TILES {
+                return Err(ParseLayoutError::RowTooLong(line.to_string()));
+            }
+
+            for (tile, char) in row.iter_mut().zip(line.chars()) {
+                *tile = match char {
+                    ' ' => None,
+                    c => Some(Tile::from_char(c).ok_or(ParseLayoutError::UnknownTile(c))?),
+                };
+            }
+        }
+
+        Ok(Layout {
+            tiles,
+            portals: vec![],
+        })
+    }
+}
+
+/// Draws a layout as text, in the same format it's parsed from.
+/// Portals aren't included, they're stored separately in the maze's header.
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in self.tiles.iter().rev() {
+            let line: String = row
+                .iter()
+                .map(|tile| tile.map(Tile::to_char).unwrap_or(' '))
+                .collect();
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+/// Who's trying to move around the layout, since not everyone can go everywhere
+#[derive(Component, Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum ActorKind {
+    PacMan,
+    Ghost,
+    /// A ghost going into or out of the ghost house, the only thing that can go through its door
+    HouseGhost,
+}
+
+/// A pair of linked locations, usually off the edge of the screen at either end of a tunnel.
+/// Actors stepping onto one portal while moving away from the other come out of the other,
+/// still moving in the same direction.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Portal(pub GridLocation, pub GridLocation);
+
+impl Portal {
+    /// How far off the screen tunnels go, so actors are out of sight when they go through
+    const MARGIN: isize = 2;
+
+    /// A tunnel running off the left and right of the screen
+    pub fn horizontal(row: isize) -> Self {
+        Portal(
+            GridLocation {
+                x: -Self::MARGIN,
+                y: row,
+            },
+            GridLocation {
+                x: WIDTH_TILES as isize + Self::MARGIN,
+                y: row,
+            },
+        )
+    }
+
+    fn exit(self, loc: GridLocation, dir: Dir) -> Option<GridLocation> {
+        let (entrance, exit) = match self {
+            Portal(a, b) if a == loc => (a, b),
+            Portal(a, b) if b == loc => (b, a),
+            _ => return None,
+        };
+
+        let heading = loc.shift(dir).to_unscaled_vec2() - loc.to_unscaled_vec2();
+        let away = entrance.to_unscaled_vec2() - exit.to_unscaled_vec2();
+        (heading.dot(away) > 0.0).then_some(exit)
+    }
+}
+
+/// A location that's off the layout, so it has no tile, not even an empty one
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct OutOfBounds(pub GridLocation);
+
+impl fmt::Display for OutOfBounds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} is off the layout", self.0)
+    }
+}
+
+impl std::error::Error for OutOfBounds {}
+
+#[derive(Debug)]
+pub enum ParseLayoutError {
+    WrongHeight(usize),
+    RowTooLong(String),
+    UnknownTile(char),
+}
+
+impl fmt::Display for ParseLayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WrongHeight(height) => {
+                write!(f, "expected {HEIGHT_TILES} rows but found {height}")
+            }
+            Self::RowTooLong(row) => {
+                write!(f, "row is longer than {WIDTH_TILES} tiles: {row:?}")
+            }
+            Self::UnknownTile(char) => write!(f, "unknown tile {char:?}"),
+        }
     }
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+impl std::error::Error for ParseLayoutError {}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
 pub enum Tile {
     Wall,
     Door,
     Dot,
     Energizer,
     PacMan,
-    Blinky,
-    Pinky,
-    Inky,
-    Clyde,
+    /// Where ghosts start, marked by an uppercase letter.
+    /// Which ghosts start where is decided by the ghost roster.
+    Ghost(char),
+}
+
+impl Tile {
+    /// The character representing this tile in a maze file
+    pub fn to_char(self) -> char {
+        match self {
+            Tile::Wall => '#',
+            Tile::Door => '-',
+            Tile::Dot => '.',
+            Tile::Energizer => 'o',
+            Tile::PacMan => '@',
+            Tile::Ghost(char) => char,
+        }
+    }
+
+    /// The tile represented by a character in a maze file
+    pub fn from_char(char: char) -> Option<Tile> {
+        match char {
+            '#' => Some(Tile::Wall),
+            '-' => Some(Tile::Door),
+            '.' => Some(Tile::Dot),
+            'o' => Some(Tile::Energizer),
+            '@' => Some(Tile::PacMan),
+            char if char.is_ascii_uppercase() => Some(Tile::Ghost(char)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROW: isize = 18;
+    const LEFT_PORTAL: GridLocation = GridLocation { x: -2, y: ROW };
+    const RIGHT_PORTAL: GridLocation = GridLocation {
+        x: WIDTH_TILES as isize + 2,
+        y: ROW,
+    };
+
+    fn tunnel() -> Layout {
+        Layout {
+            portals: vec![Portal::horizontal(ROW)],
+            ..default()
+        }
+    }
+
+    #[test]
+    fn getting_tiles_at_the_edges() {
+        let mut layout = tunnel();
+        let corner = GridLocation {
+            x: WIDTH_TILES as isize - 1,
+            y: HEIGHT_TILES as isize - 1,
+        };
+        layout.set(&corner, Some(Tile::Wall));
+
+        assert_eq!(layout.get(&GridLocation { x: 0, y: 0 }), Ok(None));
+        assert_eq!(layout.get(&corner), Ok(Some(Tile::Wall)));
+
+        for loc in [
+            GridLocation { x: -1, y: 0 },
+            GridLocation { x: 0, y: -1 },
+            GridLocation {
+                x: corner.x + 1,
+                y: corner.y,
+            },
+            GridLocation {
+                x: corner.x,
+                y: corner.y + 1,
+            },
+            LEFT_PORTAL,
+        ] {
+            assert_eq!(layout.get(&loc), Err(OutOfBounds(loc)));
+        }
+    }
+
+    #[test]
+    fn only_tunnels_are_open_off_the_layout() {
+        let layout = tunnel();
+
+        assert!(!layout.collides(&GridLocation { x: -1, y: ROW }));
+        assert!(!layout.collides(&LEFT_PORTAL));
+        assert!(!layout.collides(&RIGHT_PORTAL));
+        assert!(layout.collides(&GridLocation { x: -1, y: ROW + 1 }));
+        assert!(layout.collides(&GridLocation { x: -3, y: ROW }));
+        assert!(layout.collides(&GridLocation { x: 0, y: -1 }));
+    }
+
+    #[test]
+    fn doors_only_let_ghosts_through_going_in_or_out_of_the_house() {
+        let mut layout = tunnel();
+        let door = GridLocation { x: 13, y: 12 };
+        layout.set(&door, Some(Tile::Door));
+
+        assert!(!layout.is_walkable_for(&door, ActorKind::PacMan));
+        assert!(!layout.is_walkable_for(&door, ActorKind::Ghost));
+        assert!(layout.is_walkable_for(&door, ActorKind::HouseGhost));
+        assert!(layout.is_walkable_for(&LEFT_PORTAL, ActorKind::Ghost));
+    }
+
+    #[test]
+    fn portals_only_lead_away_from_each_other() {
+        let layout = tunnel();
+
+        assert_eq!(
+            layout.through_portal(LEFT_PORTAL, Dir::Left),
+            Some(RIGHT_PORTAL)
+        );
+        assert_eq!(
+            layout.through_portal(RIGHT_PORTAL, Dir::Right),
+            Some(LEFT_PORTAL)
+        );
+        assert_eq!(layout.through_portal(LEFT_PORTAL, Dir::Right), None);
+        assert_eq!(layout.through_portal(LEFT_PORTAL, Dir::Up), None);
+        assert_eq!(
+            layout.through_portal(GridLocation { x: -1, y: ROW }, Dir::Left),
+            None
+        );
+    }
+
+    #[test]
+    fn shifting_goes_through_portals() {
+        let layout = tunnel();
+
+        // Stepping onto a portal comes out of the other one
+        assert_eq!(
+            layout.shift(GridLocation { x: -1, y: ROW }, Dir::Left),
+            RIGHT_PORTAL
+        );
+        // Stepping off a portal that was just come out of carries on
+        assert_eq!(
+            layout.shift(RIGHT_PORTAL, Dir::Left),
+            GridLocation {
+                x: WIDTH_TILES as isize + 1,
+                y: ROW
+            }
+        );
+        // Leaving through a portal, having stepped back onto it
+        assert_eq!(
+            layout.shift(RIGHT_PORTAL, Dir::Right),
+            GridLocation { x: -1, y: ROW }
+        );
+    }
+
+    #[test]
+    fn shifting_away_from_portals_is_one_step() {
+        let layout = tunnel();
+        let loc = GridLocation { x: 5, y: 5 };
+
+        for dir in [Dir::Up, Dir::Down, Dir::Left, Dir::Right] {
+            assert_eq!(layout.shift(loc, dir), loc.shift(dir));
+        }
+    }
+
+    #[test]
+    fn distances_take_shortcuts_through_portals() {
+        let layout = tunnel();
+        let left = GridLocation { x: 0, y: ROW };
+        let right = GridLocation {
+            x: WIDTH_TILES as isize - 1,
+            y: ROW,
+        };
+
+        // Two tiles to the left portal, then three from the right one
+        assert_eq!(layout.wrapped_distance(left, right), 5.0);
+        assert_eq!(layout.wrapped_distance(right, left), 5.0);
+        assert_eq!(Layout::default().wrapped_distance(left, right), 27.0);
+    }
 }
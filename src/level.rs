@@ -1,11 +1,15 @@
-use crate::actor::ghost::{Blinky, Clyde, GhostSpawner, Inky, Pinky};
-use crate::actor::movement::{moving_left, MovementBundle, NextDir, StartLocation, BASE_SPEED};
-use crate::actor::player::{Player, PlayerDeath, PlayerDied};
-use crate::food::{Energizer, Food};
+use crate::actor::ghost::{Ghost, GhostSpawner};
+use crate::actor::movement::{moving_left, MovementBundle, NextDir, StartLocation};
+use crate::actor::player::{InputBuffer, Player, PlayerDeath, PlayerDied, PlayerNumber};
+use crate::food::{Energizer, Food, WriteEatEvent};
 use crate::grid::{Grid, GridBundle, GridLocation, Layer, SetGridMoving};
-use crate::layout::{Layout, Tile};
+use crate::layout::{ActorKind, Layout, Tile};
+use crate::maze::{CurrentMaze, Maze, MazePlaylist};
+use crate::players::PacMen;
+use bevy::ecs::system::SystemState;
 use bevy::math::Rect;
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 pub const WIDTH_TILES: usize = 28;
 pub const HEIGHT_TILES: usize = 36;
@@ -16,6 +20,14 @@ pub const HEIGHT: f32 = HEIGHT_TILES as f32 * GRID_SIZE;
 pub const SCALE: f32 = 3.0;
 pub const GRID_SIZE: f32 = 8.0;
 
+/// Multiplied with Pac-Man's sprite so each player can tell which one they are
+const PLAYER_TINTS: [Color; 4] = [
+    Color::WHITE,
+    Color::rgb(0.5, 1.0, 1.0),
+    Color::rgb(1.0, 0.6, 1.0),
+    Color::rgb(1.0, 0.6, 0.6),
+];
+
 pub const GRID: Grid = Grid {
     size: Vec2::splat(GRID_SIZE),
     offset: Vec2::ZERO,
@@ -26,16 +38,55 @@ pub struct LevelPlugin;
 impl Plugin for LevelPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<LevelAssets>()
-            .add_startup_system(setup_background)
+            .init_resource::<CurrentLevel>()
+            .init_resource::<LevelRestarted>()
+            .add_event::<UnloadLevel>()
+            .add_event::<RestartLevel>()
             .add_startup_system(create_level)
             .add_system(
                 reset_level_when_player_dies
                     .after(PlayerDeath)
                     .after(SetGridMoving),
-            );
+            )
+            .add_system(
+                start_next_level_when_cleared
+                    .label(StartLevel)
+                    .after(WriteEatEvent),
+            )
+            // After everything else, so nothing tries to change the actors that are despawned
+            .add_system_to_stage(CoreStage::PostUpdate, unload_level.before(restart_level))
+            .add_system_to_stage(CoreStage::PostUpdate, restart_level);
+    }
+}
+
+#[derive(SystemLabel)]
+pub struct StartLevel;
+
+/// The level being played, starting from 1
+#[derive(Resource, Clone, Debug, Deref, Serialize, Deserialize)]
+pub struct CurrentLevel(usize);
+
+impl Default for CurrentLevel {
+    fn default() -> Self {
+        Self(1)
     }
 }
 
+/// Whether the level was rebuilt at the end of the last frame. The rest of the game only finds out
+/// at the start of the next one, so this has to be saved along with it.
+#[derive(Resource, Default, Copy, Clone, Debug, Deref, Serialize, Deserialize)]
+pub struct LevelRestarted(bool);
+
+/// Removes everything in the level, without starting a new one
+pub struct UnloadLevel;
+
+/// Starts the current level again from the current [`Layout`]
+pub struct RestartLevel;
+
+/// Parent of every entity belonging to the current level
+#[derive(Component)]
+struct LevelRoot;
+
 #[derive(Resource)]
 struct LevelAssets {
     pac_man: Handle<TextureAtlas>,
@@ -76,19 +127,23 @@ impl FromWorld for LevelAssets {
     }
 }
 
-fn setup_background(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+const BACKGROUND_HEIGHT: f32 = HEIGHT - (TOP_MARGIN + BOTTOM_MARGIN) as f32 * GRID_SIZE;
+
+fn spawn_background(
+    bldr: &mut ChildBuilder,
+    maze: &Maze,
+    asset_server: &AssetServer,
+    texture_atlases: &mut Assets<TextureAtlas>,
 ) {
+    let start = match maze.artwork {
+        Some(start) => start,
+        None => return spawn_walls(bldr, maze),
+    };
+
     let sheet = asset_server.load("sprite_sheet.png");
 
     let mut background_atlas = TextureAtlas::new_empty(sheet, Vec2::new(680.0, 248.0));
 
-    let start = Vec2::new(228.0, 0.0);
-
-    const BACKGROUND_HEIGHT: f32 = HEIGHT - (TOP_MARGIN + BOTTOM_MARGIN) as f32 * GRID_SIZE;
-
     background_atlas.add_texture(Rect {
         min: start,
         max: start + Vec2::new(WIDTH, BACKGROUND_HEIGHT),
@@ -96,39 +151,229 @@ fn setup_background(
 
     let background_handle = texture_atlases.add(background_atlas);
 
-    commands
-        .spawn(SpriteSheetBundle {
-            texture_atlas: background_handle,
-            ..default()
-        })
-        .insert((
-            GridBundle::new(
-                Grid {
-                    size: Vec2::splat(GRID_SIZE),
-                    offset: Vec2::new(
-                        (WIDTH - GRID_SIZE) / 2.0,
-                        (BACKGROUND_HEIGHT - GRID_SIZE) / 2.0,
-                    ),
-                },
-                GridLocation {
-                    x: 0,
-                    y: BOTTOM_MARGIN as isize,
+    bldr.spawn(SpriteSheetBundle {
+        texture_atlas: background_handle,
+        ..default()
+    })
+    .insert((
+        GridBundle::new(
+            Grid {
+                size: Vec2::splat(GRID_SIZE),
+                offset: Vec2::new(
+                    (WIDTH - GRID_SIZE) / 2.0,
+                    (BACKGROUND_HEIGHT - GRID_SIZE) / 2.0,
+                ),
+            },
+            GridLocation {
+                x: 0,
+                y: BOTTOM_MARGIN as isize,
+            },
+            Layer::BACKGROUND,
+        ),
+        Name::new("Background"),
+    ));
+}
+
+fn spawn_walls(bldr: &mut ChildBuilder, maze: &Maze) {
+    for x in 0..WIDTH_TILES {
+        for y in 0..HEIGHT_TILES {
+            let loc = GridLocation {
+                x: x as isize,
+                y: y as isize,
+            };
+
+            if maze.layout.get(&loc) != Ok(Some(Tile::Wall)) {
+                continue;
+            }
+
+            bldr.spawn(SpriteBundle {
+                sprite: Sprite {
+                    color: maze.wall_color,
+                    custom_size: Some(GRID.size),
+                    ..default()
                 },
-                Layer::BACKGROUND,
-            ),
-            Name::new("Background"),
-        ));
+                ..default()
+            })
+            .insert((
+                GridBundle::new(GRID, loc, Layer::BACKGROUND),
+                Name::new("Wall"),
+            ));
+        }
+    }
 }
 
 fn create_level(
     mut commands: Commands,
+    maze: Res<CurrentMaze>,
+    level_assets: Res<LevelAssets>,
+    ghosts: Res<GhostSpawner>,
+    pac_men: Res<PacMen>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+) {
+    spawn_level(
+        &mut commands,
+        &maze,
+        &level_assets,
+        &ghosts,
+        *pac_men,
+        &asset_server,
+        &mut texture_atlases,
+    );
+}
+
+fn start_next_level_when_cleared(
+    mut restart_events: EventWriter<RestartLevel>,
+    mut level: ResMut<CurrentLevel>,
+    mut layout: ResMut<Layout>,
+    mut current_maze: ResMut<CurrentMaze>,
+    playlist: Res<MazePlaylist>,
+    roots: Query<(), With<LevelRoot>>,
+    // Frightened ghosts are also food, but don't need to be eaten to clear the level
+    food: Query<(), (With<Food>, Without<Ghost>)>,
+) {
+    if !food.is_empty() || roots.is_empty() {
+        return;
+    }
+
+    level.0 += 1;
+    let maze = playlist.maze_for_level(level.0);
+    info!("Starting level {} on {}", level.0, maze.name);
+    *layout = maze.layout.clone();
+    *current_maze = CurrentMaze(maze);
+    restart_events.send(RestartLevel);
+}
+
+fn unload_level(
+    mut commands: Commands,
+    mut unload_events: EventReader<UnloadLevel>,
+    roots: Query<Entity, With<LevelRoot>>,
+) {
+    if unload_events.iter().count() == 0 {
+        return;
+    }
+
+    for root in &roots {
+        commands.entity(root).despawn_recursive();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn restart_level(
+    mut commands: Commands,
+    mut restart_events: EventReader<RestartLevel>,
+    mut level: ResMut<CurrentLevel>,
+    mut restarted: ResMut<LevelRestarted>,
     layout: Res<Layout>,
+    mut maze: ResMut<CurrentMaze>,
     level_assets: Res<LevelAssets>,
     ghosts: Res<GhostSpawner>,
+    pac_men: Res<PacMen>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    roots: Query<Entity, With<LevelRoot>>,
+) {
+    restarted.0 = restart_events.iter().count() > 0;
+    if !restarted.0 {
+        return;
+    }
+
+    for root in &roots {
+        commands.entity(root).despawn_recursive();
+    }
+
+    maze.layout = layout.clone();
+    spawn_level(
+        &mut commands,
+        &maze,
+        &level_assets,
+        &ghosts,
+        *pac_men,
+        &asset_server,
+        &mut texture_atlases,
+    );
+
+    // Resets anything else that depends on the level, without changing it
+    level.set_changed();
+}
+
+/// Rebuilds the level straight away from the current maze, with everything back where it starts,
+/// e.g. before loading a snapshot from another level
+pub(crate) fn rebuild_level(world: &mut World) {
+    let mut state: SystemState<RebuildLevel> = SystemState::new(world);
+    let (mut commands, maze, level_assets, ghosts, pac_men, asset_server, mut atlases, roots) =
+        state.get_mut(world);
+
+    for root in &roots {
+        commands.entity(root).despawn_recursive();
+    }
+    spawn_level(
+        &mut commands,
+        &maze,
+        &level_assets,
+        &ghosts,
+        *pac_men,
+        &asset_server,
+        &mut atlases,
+    );
+
+    state.apply(world);
+}
+
+type RebuildLevel<'w, 's> = (
+    Commands<'w, 's>,
+    Res<'w, CurrentMaze>,
+    Res<'w, LevelAssets>,
+    Res<'w, GhostSpawner>,
+    Res<'w, PacMen>,
+    Res<'w, AssetServer>,
+    ResMut<'w, Assets<TextureAtlas>>,
+    Query<'w, 's, Entity, With<LevelRoot>>,
+);
+
+type RespawnFood<'w, 's> = (
+    Commands<'w, 's>,
+    Res<'w, Layout>,
+    Res<'w, LevelAssets>,
+    Query<'w, 's, Entity, With<LevelRoot>>,
+);
+
+/// Puts dots and energizers back on tiles where the layout has them, e.g. when loading a snapshot
+/// from before they were eaten
+pub(crate) fn respawn_food(world: &mut World, locations: &[GridLocation]) {
+    let mut state: SystemState<RespawnFood> = SystemState::new(world);
+    let (mut commands, layout, level_assets, roots) = state.get_mut(world);
+
+    let root = match roots.iter().next() {
+        Some(root) => root,
+        None => return,
+    };
+    commands.entity(root).with_children(|bldr| {
+        for loc in locations {
+            if let Ok(Some(tile)) = layout.get(loc) {
+                spawn_food(bldr, *loc, tile, &level_assets);
+            }
+        }
+    });
+
+    state.apply(world);
+}
+
+fn spawn_level(
+    commands: &mut Commands,
+    maze: &Maze,
+    level_assets: &LevelAssets,
+    ghosts: &GhostSpawner,
+    pac_men: PacMen,
+    asset_server: &AssetServer,
+    texture_atlases: &mut Assets<TextureAtlas>,
 ) {
     commands
-        .spawn((SpatialBundle::default(), Name::new("Level")))
-        .with_children(|bldr| spawn_level_entities(bldr, &layout, &level_assets, &ghosts));
+        .spawn((SpatialBundle::default(), Name::new("Level"), LevelRoot))
+        .with_children(|bldr| {
+            spawn_background(bldr, maze, asset_server, texture_atlases);
+            spawn_level_entities(bldr, &maze.layout, level_assets, ghosts, pac_men);
+        });
 }
 
 fn reset_level_when_player_dies(
@@ -150,6 +395,7 @@ fn spawn_level_entities(
     layout: &Layout,
     level_assets: &LevelAssets,
     ghosts: &GhostSpawner,
+    pac_men: PacMen,
 ) {
     for x in 0..WIDTH_TILES {
         for y in 0..HEIGHT_TILES {
@@ -159,17 +405,16 @@ fn spawn_level_entities(
             };
 
             match layout.get(&loc) {
-                Some(Tile::Dot) => {
-                    spawn_food(bldr, loc, "Dot", 0, level_assets, 10, ());
+                Ok(Some(tile @ (Tile::Dot | Tile::Energizer))) => {
+                    spawn_food(bldr, loc, tile, level_assets);
                 }
-                Some(Tile::Energizer) => {
-                    spawn_food(bldr, loc, "Energizer", 1, level_assets, 50, (Energizer,));
+                Ok(Some(Tile::PacMan)) => {
+                    // Every Pac-Man starts on the same tile
+                    for number in 0..*pac_men {
+                        spawn_pac_man(bldr, loc, level_assets, PlayerNumber(number));
+                    }
                 }
-                Some(Tile::PacMan) => spawn_pac_man(bldr, loc, level_assets),
-                Some(Tile::Blinky) => ghosts.spawn(bldr, Blinky, loc),
-                Some(Tile::Pinky) => ghosts.spawn(bldr, Pinky, loc),
-                Some(Tile::Inky) => ghosts.spawn(bldr, Inky, loc),
-                Some(Tile::Clyde) => ghosts.spawn(bldr, Clyde, loc),
+                Ok(Some(Tile::Ghost(spawn))) => ghosts.spawn(bldr, spawn, loc),
                 _ => {}
             }
         }
@@ -185,35 +430,48 @@ pub struct GridEntity {
     pub _visiblity: VisibilityBundle,
 }
 
-fn spawn_pac_man(commands: &mut ChildBuilder, location: GridLocation, level_assets: &LevelAssets) {
+fn spawn_pac_man(
+    commands: &mut ChildBuilder,
+    location: GridLocation,
+    level_assets: &LevelAssets,
+    number: PlayerNumber,
+) {
     commands
         .spawn((
             GridEntity {
                 name: Name::new("Pac-Man"),
+                sprite: TextureAtlasSprite {
+                    color: PLAYER_TINTS[*number % PLAYER_TINTS.len()],
+                    ..default()
+                },
                 texture_atlas: level_assets.pac_man.clone(),
                 grid: GridBundle::new(GRID, location, default()),
                 ..default()
             },
-            MovementBundle {
-                speed: BASE_SPEED * 0.8,
-                ..default()
-            },
+            MovementBundle::default(),
             NextDir::default(),
+            InputBuffer::default(),
+            ActorKind::PacMan,
             Player,
+            number,
         ))
         .insert(moving_left(location));
 }
 
+/// Spawns a dot or energizer, if that's what the tile is
 fn spawn_food(
     commands: &mut ChildBuilder,
     location: GridLocation,
-    name: &'static str,
-    sprite_index: usize,
+    tile: Tile,
     level_assets: &LevelAssets,
-    points: u32,
-    bundle: impl Bundle,
 ) {
-    commands.spawn((
+    let (name, sprite_index, points) = match tile {
+        Tile::Dot => ("Dot", 0, 10),
+        Tile::Energizer => ("Energizer", 1, 50),
+        _ => return,
+    };
+
+    let mut food = commands.spawn((
         GridEntity {
             name: Name::new(name),
             sprite: TextureAtlasSprite::new(sprite_index),
@@ -222,6 +480,8 @@ fn spawn_food(
             ..default()
         },
         Food { points },
-        bundle,
     ));
+    if tile == Tile::Energizer {
+        food.insert(Energizer);
+    }
 }
@@ -0,0 +1,65 @@
+//! Pac-Man, built with Bevy.
+//!
+//! The game rules run without a window through [`GamePlugins`], which is how [`gym`] and [`simulate`]
+//! drive headless games.
+
+pub mod actor;
+pub mod controls;
+pub mod diagnostics;
+pub mod editor;
+pub mod food;
+pub mod from_env;
+pub mod grid;
+pub mod gym;
+pub mod high_scores;
+pub mod layout;
+pub mod level;
+pub mod maze;
+pub mod netplay;
+pub mod players;
+pub mod random;
+pub mod score;
+pub mod simulate;
+pub mod snapshot;
+pub mod telemetry;
+pub mod text;
+pub mod ui;
+
+use bevy::app::PluginGroupBuilder;
+use bevy::prelude::*;
+
+use crate::actor::autopilot::AutopilotPlugin;
+use crate::actor::ghost::GhostPlugin;
+use crate::actor::mode::ModePlugin;
+use crate::actor::movement::MovementPlugin;
+use crate::actor::player::PlayerPlugin;
+use crate::actor::speed::SpeedPlugin;
+use crate::controls::ControlsPlugin;
+use crate::food::FoodPlugin;
+use crate::grid::GridPlugin;
+use crate::layout::LayoutPlugin;
+use crate::level::LevelPlugin;
+use crate::players::PlayersPlugin;
+use crate::score::ScorePlugin;
+
+/// Everything needed to play the game, apart from drawing it and reading the keyboard
+pub struct GamePlugins;
+
+impl PluginGroup for GamePlugins {
+    fn build(self) -> PluginGroupBuilder {
+        PluginGroupBuilder::start::<Self>()
+            .add(ControlsPlugin)
+            .add(GridPlugin)
+            .add(PlayerPlugin)
+            .add(AutopilotPlugin)
+            .add(FoodPlugin)
+            .add(ScorePlugin)
+            .add(MovementPlugin)
+            .add(SpeedPlugin)
+            .add(LevelPlugin)
+            .add(LayoutPlugin)
+            .add(GhostPlugin)
+            .add(ModePlugin)
+            .add(PlayersPlugin)
+    }
+}
@@ -1,38 +1,63 @@
-mod actor;
-mod diagnostics;
-mod food;
-mod from_env;
-mod grid;
-mod layout;
-mod level;
-mod score;
-mod text;
-mod ui;
-
-use crate::actor::movement::MovementPlugin;
-use crate::actor::player::PlayerPlugin;
-use crate::diagnostics::InspectorPlugin;
-use crate::food::FoodPlugin;
-use crate::grid::GridPlugin;
-use crate::level::{LevelPlugin, HEIGHT, HEIGHT_TILES, SCALE, WIDTH};
-use crate::score::ScorePlugin;
-use crate::ui::UIPlugin;
-use actor::ghost::GhostPlugin;
-use actor::mode::ModePlugin;
 use bevy::app::AppExit;
+use bevy::core::{CorePlugin, TaskPoolOptions};
 use bevy::prelude::*;
 use bevy::render::camera::ScalingMode;
-use from_env::FromEnv;
-use layout::LayoutPlugin;
-use level::GRID_SIZE;
+use pac_man::controls::{Action, Controls};
+use pac_man::diagnostics::InspectorPlugin;
+use pac_man::editor::EditorPlugin;
+use pac_man::from_env::FromEnv;
+use pac_man::high_scores::HighScoresPlugin;
+use pac_man::level::{GRID_SIZE, HEIGHT, SCALE, WIDTH};
+use pac_man::netplay::{self, NetplayPlugin};
+use pac_man::telemetry::TelemetryPlugin;
+use pac_man::ui::UIPlugin;
+use pac_man::{gym, simulate, GamePlugins};
 
 fn main() {
-    App::new()
-        .insert_resource(ClearColor(Color::BLACK))
+    match std::env::args().nth(1).as_deref() {
+        Some("gym") => {
+            if let Err(err) = gym::serve(std::io::stdin().lock(), std::io::stdout().lock()) {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some("simulate") => {
+            if let Err(err) = run_simulation() {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some("netplay") => {
+            if let Err(err) = run_netplay() {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        _ => {}
+    }
+
+    window_app(TaskPoolOptions::default())
+        .add_plugin(HighScoresPlugin)
+        .add_plugin(InspectorPlugin)
+        .add_plugins(GamePlugins)
+        .add_plugin(EditorPlugin)
+        .add_plugin(TelemetryPlugin)
+        .run();
+}
+
+/// Everything needed to draw the game in a window, apart from the game itself
+fn window_app(task_pool_options: TaskPoolOptions) -> App {
+    let mut app = App::new();
+
+    app.insert_resource(ClearColor(Color::BLACK))
         .add_startup_system(setup_camera)
         .add_system(exit_game)
         .add_plugins(
             DefaultPlugins
+                .set(CorePlugin { task_pool_options })
                 .set(ImagePlugin::default_nearest())
                 .set(WindowPlugin {
                     window: WindowDescriptor {
@@ -44,18 +69,35 @@ fn main() {
                     ..default()
                 }),
         )
-        .add_plugin(GridPlugin)
-        .add_plugin(UIPlugin)
-        .add_plugin(InspectorPlugin)
-        .add_plugin(PlayerPlugin)
-        .add_plugin(FoodPlugin)
-        .add_plugin(ScorePlugin)
-        .add_plugin(MovementPlugin)
-        .add_plugin(LevelPlugin)
-        .add_plugin(LayoutPlugin)
-        .add_plugin(GhostPlugin)
-        .add_plugin(ModePlugin)
+        .add_plugin(UIPlugin);
+
+    app
+}
+
+fn run_simulation() -> Result<(), Box<dyn std::error::Error>> {
+    let options = simulate::Options::parse(std::env::args().skip(2))?;
+    let stats = simulate::run(&options);
+    simulate::write_stats(&stats, options.format, std::io::stdout().lock())?;
+    Ok(())
+}
+
+fn run_netplay() -> Result<(), Box<dyn std::error::Error>> {
+    let options = netplay::Options::parse(std::env::args().skip(2))?;
+    let session = netplay::Session::bind(&options)?;
+
+    if options.headless {
+        netplay::headless_app(session).run();
+        return Ok(());
+    }
+
+    // Without the debug keys or the editor, which would only change the game on this side, and
+    // on one thread, so systems that could run in either order run in the same one on both sides
+    window_app(TaskPoolOptions::with_num_threads(1))
+        .add_plugins(GamePlugins)
+        .insert_resource(session)
+        .add_plugin(NetplayPlugin)
         .run();
+    Ok(())
 }
 
 fn setup_camera(mut commands: Commands) {
@@ -77,8 +119,8 @@ fn setup_camera(mut commands: Commands) {
     });
 }
 
-fn exit_game(input: Res<Input<KeyCode>>, mut exit: EventWriter<AppExit>) {
-    if input.pressed(KeyCode::Escape) {
+fn exit_game(controls: Res<Controls>, input: Res<Input<KeyCode>>, mut exit: EventWriter<AppExit>) {
+    if controls.pressed(&input, Action::Quit) {
         exit.send(AppExit);
     }
 }
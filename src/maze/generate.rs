@@ -0,0 +1,386 @@
+use std::collections::{HashSet, VecDeque};
+
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::actor::movement::Dir;
+use crate::grid::GridLocation;
+use crate::layout::{Layout, Portal, Tile};
+use crate::level::WIDTH_TILES;
+
+use super::Maze;
+
+// The maze is built from a lattice of corridors, which are then randomly removed.
+// Columns and rows are spaced so walls between corridors are always at least two tiles thick.
+const LEFT_COLUMNS: [isize; 4] = [1, 5, 9, 12];
+const ROWS: [isize; 9] = [3, 7, 11, 14, 17, 20, 24, 28, 31];
+const TOP_WALL: isize = 32;
+const BOTTOM_WALL: isize = 2;
+
+const HOUSE_MIN: GridLocation = GridLocation { x: 10, y: 15 };
+const HOUSE_MAX: GridLocation = GridLocation { x: 17, y: 19 };
+const DOORS: [GridLocation; 2] = [GridLocation { x: 13, y: 19 }, GridLocation { x: 14, y: 19 }];
+
+// Dots are left out of the area around the ghost house, like in the arcade
+const NO_DOTS_MIN: GridLocation = GridLocation { x: 9, y: 14 };
+const NO_DOTS_MAX: GridLocation = GridLocation { x: 18, y: 20 };
+
+const PAC_MAN: GridLocation = GridLocation { x: 13, y: 11 };
+const GHOSTS: [(Tile, GridLocation); 4] = [
+    (Tile::Ghost('B'), GridLocation { x: 13, y: 20 }),
+    (Tile::Ghost('I'), GridLocation { x: 12, y: 14 }),
+    (Tile::Ghost('P'), GridLocation { x: 13, y: 14 }),
+    (Tile::Ghost('C'), GridLocation { x: 15, y: 14 }),
+];
+
+const ENERGIZERS: [GridLocation; 2] = [GridLocation { x: 1, y: 7 }, GridLocation { x: 1, y: 28 }];
+
+/// Corridors that are always kept, so actors can leave their spawn points.
+/// Their mirror images are kept too.
+const FIXED_CORRIDORS: [Corridor; 5] = [
+    Corridor(GridLocation { x: 9, y: 20 }, GridLocation { x: 12, y: 20 }),
+    Corridor(GridLocation { x: 12, y: 20 }, GridLocation { x: 15, y: 20 }),
+    Corridor(GridLocation { x: 9, y: 14 }, GridLocation { x: 12, y: 14 }),
+    Corridor(GridLocation { x: 12, y: 14 }, GridLocation { x: 15, y: 14 }),
+    Corridor(GridLocation { x: 12, y: 11 }, GridLocation { x: 15, y: 11 }),
+];
+
+const WALL_COLORS: [Color; 5] = [
+    Color::rgb(1.0, 0.72, 0.68),
+    Color::rgb(0.28, 0.72, 1.0),
+    Color::rgb(0.87, 0.59, 0.32),
+    Color::rgb(0.13, 0.13, 1.0),
+    Color::rgb(1.0, 0.72, 0.32),
+];
+
+/// A straight corridor between two lattice points, with the bottom-left point first
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+struct Corridor(GridLocation, GridLocation);
+
+impl Corridor {
+    fn new(a: GridLocation, b: GridLocation) -> Self {
+        if (a.x, a.y) <= (b.x, b.y) {
+            Corridor(a, b)
+        } else {
+            Corridor(b, a)
+        }
+    }
+
+    fn mirror(self) -> Self {
+        Corridor::new(mirror(self.0), mirror(self.1))
+    }
+
+    fn tiles(self) -> impl Iterator<Item = GridLocation> {
+        let Corridor(a, b) = self;
+        (a.x..=b.x).flat_map(move |x| (a.y..=b.y).map(move |y| GridLocation { x, y }))
+    }
+}
+
+fn mirror(loc: GridLocation) -> GridLocation {
+    GridLocation {
+        x: WIDTH_TILES as isize - 1 - loc.x,
+        y: loc.y,
+    }
+}
+
+fn in_box(loc: GridLocation, min: GridLocation, max: GridLocation) -> bool {
+    (min.x..=max.x).contains(&loc.x) && (min.y..=max.y).contains(&loc.y)
+}
+
+/// Generates a symmetric maze with no dead ends.
+/// The same seed always produces the same maze.
+pub fn generate(seed: u64) -> Maze {
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let columns: Vec<isize> = LEFT_COLUMNS
+        .into_iter()
+        .chain(
+            LEFT_COLUMNS
+                .into_iter()
+                .rev()
+                .map(|x| WIDTH_TILES as isize - 1 - x),
+        )
+        .collect();
+
+    let is_node = |loc: GridLocation| !in_box(loc, HOUSE_MIN, HOUSE_MAX);
+
+    let mut nodes = HashSet::new();
+    let mut corridors = HashSet::new();
+
+    for (ix, &x) in columns.iter().enumerate() {
+        for (iy, &y) in ROWS.iter().enumerate() {
+            let loc = GridLocation { x, y };
+            if !is_node(loc) {
+                continue;
+            }
+
+            nodes.insert(loc);
+
+            let right = columns.get(ix + 1).map(|&x| GridLocation { x, y });
+            let up = ROWS.get(iy + 1).map(|&y| GridLocation { x, y });
+
+            for next in right.into_iter().chain(up).filter(|&next| is_node(next)) {
+                corridors.insert(Corridor::new(loc, next));
+            }
+        }
+    }
+
+    // Tunnels can't be on the outermost rows, which have no walls to enclose them
+    let num_tunnels = rng.gen_range(1..=2);
+    let tunnels: Vec<isize> = ROWS[1..ROWS.len() - 1]
+        .choose_multiple(&mut rng, num_tunnels)
+        .copied()
+        .collect();
+
+    // Each corridor on the left is removed along with its mirror image on the right
+    let mut candidates: Vec<Corridor> = corridors
+        .iter()
+        .copied()
+        .filter(|corridor| corridor.0.x < WIDTH_TILES as isize / 2)
+        .filter(|corridor| !FIXED_CORRIDORS.contains(corridor))
+        .filter(|corridor| !FIXED_CORRIDORS.contains(&corridor.mirror()))
+        .collect();
+    // Sort first so the shuffle only depends on the seed, not on the hash set's order
+    candidates.sort_by_key(|Corridor(a, b)| (a.x, a.y, b.x, b.y));
+    candidates.shuffle(&mut rng);
+
+    let to_remove = (candidates.len() as f32 * rng.gen_range(0.3..0.5)) as usize;
+    let mut removed = 0;
+
+    for corridor in candidates {
+        if removed == to_remove {
+            break;
+        }
+
+        corridors.remove(&corridor);
+        corridors.remove(&corridor.mirror());
+
+        if is_valid(&nodes, &corridors, &tunnels) {
+            removed += 1;
+        } else {
+            corridors.insert(corridor);
+            corridors.insert(corridor.mirror());
+        }
+    }
+
+    let mut layout = draw(&corridors, &tunnels);
+    layout.portals = tunnels.iter().copied().map(Portal::horizontal).collect();
+
+    let fruit_entries = tunnels
+        .iter()
+        .flat_map(|&y| {
+            [
+                GridLocation { x: 0, y },
+                GridLocation {
+                    x: WIDTH_TILES as isize - 1,
+                    y,
+                },
+            ]
+        })
+        .collect();
+
+    Maze {
+        name: format!("Generated #{seed}"),
+        layout,
+        wall_color: *WALL_COLORS.choose(&mut rng).unwrap(),
+        fruit_entries,
+        artwork: None,
+    }
+}
+
+/// Every lattice point must be reachable and have at least two ways out
+fn is_valid(
+    nodes: &HashSet<GridLocation>,
+    corridors: &HashSet<Corridor>,
+    tunnels: &[isize],
+) -> bool {
+    let neighbours = |loc: GridLocation| {
+        let corridor_ends = corridors.iter().filter_map(move |&Corridor(a, b)| {
+            if a == loc {
+                Some(b)
+            } else if b == loc {
+                Some(a)
+            } else {
+                None
+            }
+        });
+
+        let at_edge = loc.x == LEFT_COLUMNS[0] || mirror(loc).x == LEFT_COLUMNS[0];
+        let through_tunnel = (at_edge && tunnels.contains(&loc.y)).then(|| mirror(loc));
+
+        corridor_ends.chain(through_tunnel)
+    };
+
+    if nodes.iter().any(|&node| neighbours(node).count() < 2) {
+        return false;
+    }
+
+    let start = match nodes.iter().next() {
+        Some(start) => *start,
+        None => return false,
+    };
+
+    let mut seen = HashSet::from([start]);
+    let mut queue = VecDeque::from([start]);
+
+    while let Some(loc) = queue.pop_front() {
+        for next in neighbours(loc) {
+            if seen.insert(next) {
+                queue.push_back(next);
+            }
+        }
+    }
+
+    seen.len() == nodes.len()
+}
+
+fn draw(corridors: &HashSet<Corridor>, tunnels: &[isize]) -> Layout {
+    let mut layout = Layout::default();
+
+    for x in 0..WIDTH_TILES as isize {
+        for y in BOTTOM_WALL..=TOP_WALL {
+            layout.set(&GridLocation { x, y }, Some(Tile::Wall));
+        }
+    }
+
+    for tile in corridors.iter().flat_map(|corridor| corridor.tiles()) {
+        let dot = !in_box(tile, NO_DOTS_MIN, NO_DOTS_MAX);
+        layout.set(&tile, dot.then_some(Tile::Dot));
+    }
+
+    for &y in tunnels {
+        layout.set(&GridLocation { x: 0, y }, None);
+        layout.set(
+            &GridLocation {
+                x: WIDTH_TILES as isize - 1,
+                y,
+            },
+            None,
+        );
+    }
+
+    for x in HOUSE_MIN.x + 1..HOUSE_MAX.x {
+        for y in HOUSE_MIN.y + 1..HOUSE_MAX.y {
+            layout.set(&GridLocation { x, y }, None);
+        }
+    }
+
+    for door in DOORS {
+        layout.set(&door, Some(Tile::Door));
+    }
+
+    for energizer in ENERGIZERS {
+        layout.set(&energizer, Some(Tile::Energizer));
+        layout.set(&mirror(energizer), Some(Tile::Energizer));
+    }
+
+    layout.set(&PAC_MAN, Some(Tile::PacMan));
+    layout.set(&PAC_MAN.shift(Dir::Right), None);
+
+    for (ghost, loc) in GHOSTS {
+        layout.set(&loc, Some(ghost));
+    }
+
+    layout
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::HEIGHT_TILES;
+
+    const SEEDS: std::ops::Range<u64> = 0..20;
+
+    fn locations() -> impl Iterator<Item = GridLocation> {
+        (0..HEIGHT_TILES as isize)
+            .flat_map(|y| (0..WIDTH_TILES as isize).map(move |x| GridLocation { x, y }))
+    }
+
+    fn tiles(maze: &Maze) -> Vec<Option<Tile>> {
+        locations()
+            .map(|loc| maze.layout.get(&loc).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn the_same_seed_makes_the_same_maze() {
+        for seed in SEEDS {
+            let (a, b) = (generate(seed), generate(seed));
+            assert!(tiles(&a) == tiles(&b), "seed {seed}");
+            assert_eq!(a.layout.portals, b.layout.portals, "seed {seed}");
+        }
+
+        assert!(tiles(&generate(1)) != tiles(&generate(2)));
+    }
+
+    #[test]
+    fn mazes_are_symmetric_apart_from_the_spawns() {
+        let is_spawn = |tile| matches!(tile, Some(Tile::PacMan | Tile::Ghost(_)));
+
+        for seed in SEEDS {
+            let maze = generate(seed);
+
+            for loc in locations() {
+                let tile = maze.layout.get(&loc).unwrap();
+                let mirrored = maze.layout.get(&mirror(loc)).unwrap();
+                if !is_spawn(tile) && !is_spawn(mirrored) {
+                    assert!(tile == mirrored, "seed {seed} at {loc:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn every_dot_can_be_reached() {
+        for seed in SEEDS {
+            let maze = generate(seed);
+            let open = |loc: &GridLocation| maze.layout.get(loc) != Ok(Some(Tile::Wall));
+
+            let start = locations()
+                .find(|loc| maze.layout.get(loc) == Ok(Some(Tile::PacMan)))
+                .unwrap();
+            let mut seen = HashSet::from([start]);
+            let mut queue = VecDeque::from([start]);
+
+            while let Some(loc) = queue.pop_front() {
+                for dir in [Dir::Up, Dir::Down, Dir::Left, Dir::Right] {
+                    let mut next = loc.shift(dir);
+                    // Tunnels wrap around the screen
+                    next.x = next.x.rem_euclid(WIDTH_TILES as isize);
+                    if (0..HEIGHT_TILES as isize).contains(&next.y)
+                        && open(&next)
+                        && seen.insert(next)
+                    {
+                        queue.push_back(next);
+                    }
+                }
+            }
+
+            for loc in locations() {
+                if matches!(maze.layout.get(&loc), Ok(Some(Tile::Dot | Tile::Energizer))) {
+                    assert!(seen.contains(&loc), "seed {seed}: {loc:?} can't be reached");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tunnels_lead_off_both_sides() {
+        for seed in SEEDS {
+            let maze = generate(seed);
+
+            assert!((1..=2).contains(&maze.layout.portals.len()), "seed {seed}");
+            for Portal(a, _) in &maze.layout.portals {
+                let y = a.y;
+                for x in [0, WIDTH_TILES as isize - 1] {
+                    assert!(
+                        maze.layout.get(&GridLocation { x, y }) == Ok(None),
+                        "seed {seed}"
+                    );
+                }
+            }
+        }
+    }
+}
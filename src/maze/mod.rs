@@ -0,0 +1,422 @@
+mod generate;
+mod validate;
+
+use std::fmt;
+use std::str::FromStr;
+
+use bevy::prelude::*;
+
+use crate::from_env::FromEnv;
+use crate::grid::GridLocation;
+use crate::layout::{Layout, ParseLayoutError, Portal};
+
+pub use generate::generate;
+pub use validate::{validate, Problem};
+
+/// A board to play a level on, along with how it looks.
+///
+/// Mazes are stored as text files: a header of `key: value` lines, followed by a `layout:` line
+/// and then the tiles, one row per line with the top row first.
+/// Locations in the header use grid coordinates, with `0,0` at the bottom-left.
+/// Tunnels are written as `portals:` pairs of locations joined with `/`.
+#[derive(Clone)]
+pub struct Maze {
+    pub name: String,
+    pub layout: Layout,
+    pub wall_color: Color,
+    /// Where bonus fruit enters the maze
+    pub fruit_entries: Vec<GridLocation>,
+    /// Position of pre-drawn artwork for this maze on the sprite sheet.
+    /// Mazes without artwork have their walls drawn tile-by-tile in `wall_color`.
+    pub artwork: Option<Vec2>,
+}
+
+impl Maze {
+    pub fn classic() -> Self {
+        let mut layout = Layout::from_bmp(include_bytes!("../../assets/level.bmp"));
+        layout.portals = vec![Portal::horizontal(18)];
+
+        Self {
+            name: "Classic".to_string(),
+            layout,
+            wall_color: Color::rgb_u8(0x21, 0x21, 0xff),
+            fruit_entries: vec![GridLocation { x: 13, y: 15 }],
+            artwork: Some(Vec2::new(228.0, 0.0)),
+        }
+    }
+}
+
+impl FromStr for Maze {
+    type Err = ParseMazeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (header, layout) = s
+            .split_once("layout:\n")
+            .ok_or(ParseMazeError::MissingLayout)?;
+
+        let mut name = None;
+        let mut wall_color = None;
+        let mut portals = vec![];
+        let mut fruit_entries = vec![];
+
+        for line in header.lines().filter(|line| !line.trim().is_empty()) {
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| ParseMazeError::InvalidLine(line.to_string()))?;
+            let value = value.trim();
+            let invalid = || ParseMazeError::InvalidValue {
+                key: key.to_string(),
+                value: value.to_string(),
+            };
+
+            match key {
+                "name" => name = Some(value.to_string()),
+                "wall_color" => wall_color = Some(Color::hex(value).map_err(|_| invalid())?),
+                "portals" => {
+                    portals = value
+                        .split_whitespace()
+                        .map(|portal| parse_portal(portal).ok_or_else(invalid))
+                        .collect::<Result<_, _>>()?
+                }
+                "fruit" => {
+                    fruit_entries = value
+                        .split_whitespace()
+                        .map(|loc| parse_location(loc).ok_or_else(invalid))
+                        .collect::<Result<_, _>>()?
+                }
+                _ => return Err(ParseMazeError::UnknownKey(key.to_string())),
+            }
+        }
+
+        let mut layout: Layout = layout.parse()?;
+        layout.portals = portals;
+
+        Ok(Self {
+            name: name.ok_or(ParseMazeError::MissingKey("name"))?,
+            layout,
+            wall_color: wall_color.ok_or(ParseMazeError::MissingKey("wall_color"))?,
+            fruit_entries,
+            artwork: None,
+        })
+    }
+}
+
+impl fmt::Display for Maze {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [r, g, b, _] = self
+            .wall_color
+            .as_rgba_f32()
+            .map(|c| (c * 255.0).round() as u8);
+
+        writeln!(f, "name: {}", self.name)?;
+        writeln!(f, "wall_color: {r:02x}{g:02x}{b:02x}")?;
+
+        if !self.layout.portals.is_empty() {
+            let portals: Vec<String> = self
+                .layout
+                .portals
+                .iter()
+                .map(|Portal(a, b)| format!("{}/{}", format_location(a), format_location(b)))
+                .collect();
+            writeln!(f, "portals: {}", portals.join(" "))?;
+        }
+
+        if !self.fruit_entries.is_empty() {
+            let fruit: Vec<String> = self.fruit_entries.iter().map(format_location).collect();
+            writeln!(f, "fruit: {}", fruit.join(" "))?;
+        }
+
+        write!(f, "\nlayout:\n{}", self.layout)
+    }
+}
+
+fn parse_portal(s: &str) -> Option<Portal> {
+    let (a, b) = s.split_once('/')?;
+    Some(Portal(parse_location(a)?, parse_location(b)?))
+}
+
+fn format_location(loc: &GridLocation) -> String {
+    format!("{},{}", loc.x, loc.y)
+}
+
+pub fn parse_location(s: &str) -> Option<GridLocation> {
+    let (x, y) = s.split_once(',')?;
+    Some(GridLocation {
+        x: x.parse().ok()?,
+        y: y.parse().ok()?,
+    })
+}
+
+#[derive(Debug)]
+pub enum ParseMazeError {
+    MissingLayout,
+    MissingKey(&'static str),
+    UnknownKey(String),
+    InvalidLine(String),
+    InvalidValue { key: String, value: String },
+    Layout(ParseLayoutError),
+}
+
+impl From<ParseLayoutError> for ParseMazeError {
+    fn from(err: ParseLayoutError) -> Self {
+        Self::Layout(err)
+    }
+}
+
+impl fmt::Display for ParseMazeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingLayout => write!(f, "missing 'layout:' section"),
+            Self::MissingKey(key) => write!(f, "missing '{key}'"),
+            Self::UnknownKey(key) => write!(f, "unknown key '{key}'"),
+            Self::InvalidLine(line) => write!(f, "expected 'key: value' but found {line:?}"),
+            Self::InvalidValue { key, value } => write!(f, "invalid {key} {value:?}"),
+            Self::Layout(err) => write!(f, "invalid layout: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ParseMazeError {}
+
+/// The maze for the level being played.
+/// Its layout is as it was at the start of the level, see [`Layout`] for the live state.
+#[derive(Resource, Deref, DerefMut)]
+pub struct CurrentMaze(pub Maze);
+
+/// Which maze to play on each level.
+#[derive(Resource)]
+pub enum MazePlaylist {
+    Rotation {
+        mazes: Vec<Maze>,
+        /// Pairs of (maze index, number of levels to play it for)
+        schedule: Vec<(usize, usize)>,
+        /// Index into `schedule` to go back to after reaching the end
+        repeat_from: usize,
+    },
+    /// A new generated maze every level, forever
+    Endless { seed: u64 },
+}
+
+impl MazePlaylist {
+    pub fn rotation(mazes: Vec<Maze>, schedule: Vec<(usize, usize)>, repeat_from: usize) -> Self {
+        assert!(repeat_from < schedule.len(), "Playlist must repeat");
+        for (maze, levels) in &schedule {
+            assert!(*maze < mazes.len(), "No maze with index {maze}");
+            assert!(
+                *levels > 0,
+                "Every maze must be played for at least one level"
+            );
+        }
+
+        Self::Rotation {
+            mazes,
+            schedule,
+            repeat_from,
+        }
+    }
+
+    /// The maze for a level, where the first level is 1
+    pub fn maze_for_level(&self, level: usize) -> Maze {
+        let (mazes, schedule, repeat_from) = match self {
+            Self::Rotation {
+                mazes,
+                schedule,
+                repeat_from,
+            } => (mazes, schedule, *repeat_from),
+            Self::Endless { seed } => return generate(seed.wrapping_add(level as u64)),
+        };
+
+        let mut remaining = level.saturating_sub(1);
+        let mut index = 0;
+
+        loop {
+            let (maze, levels) = schedule[index];
+            if remaining < levels {
+                return mazes[maze].clone();
+            }
+
+            remaining -= levels;
+            index += 1;
+            if index == schedule.len() {
+                index = repeat_from;
+            }
+        }
+    }
+}
+
+/// Cycles mazes like Ms. Pac-Man: each new maze is played for longer than the last,
+/// then the final two alternate every four levels.
+impl Default for MazePlaylist {
+    fn default() -> Self {
+        let mazes = vec![
+            Maze::classic(),
+            load(include_str!("../../assets/mazes/pinkys_parlour.maze")),
+            load(include_str!("../../assets/mazes/inkys_island.maze")),
+            load(include_str!("../../assets/mazes/clydes_canyon.maze")),
+        ];
+
+        Self::rotation(mazes, vec![(0, 2), (1, 3), (2, 4), (3, 4)], 2)
+    }
+}
+
+impl FromEnv for MazePlaylist {
+    fn with_env_overrides(self) -> Self {
+        if let Some(seed) = get_maze_seed() {
+            return Self::Endless { seed };
+        }
+
+        let path = match get_maze_file() {
+            Some(path) => path,
+            None => return self,
+        };
+
+        let maze = match std::fs::read_to_string(&path) {
+            Ok(maze) => maze.parse::<Maze>(),
+            // Not saved from the editor yet
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return self,
+            Err(err) => {
+                error!("Can't read maze {path}, playing the usual mazes instead: {err}");
+                return self;
+            }
+        };
+
+        match maze {
+            Ok(maze) => Self::rotation(vec![maze], vec![(0, 1)], 0),
+            Err(err) => {
+                error!("Invalid maze {path}, playing the usual mazes instead: {err}");
+                self
+            }
+        }
+    }
+}
+
+fn get_maze_seed() -> Option<u64> {
+    std::env::var("MAZE_SEED").ok()?.parse().ok()
+}
+
+/// A maze file to play instead of the usual playlist, which is also where the editor saves to
+pub fn get_maze_file() -> Option<String> {
+    std::env::var("MAZE_FILE").ok()
+}
+
+fn load(maze: &str) -> Maze {
+    maze.parse()
+        .unwrap_or_else(|err| panic!("Invalid maze: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::Tile;
+    use crate::level::HEIGHT_TILES;
+
+    /// A maze file with the given header, and a layout with a wall in the bottom-left corner
+    fn maze_file(header: &str) -> String {
+        format!("{header}\nlayout:\n{}#\n", "\n".repeat(HEIGHT_TILES - 1))
+    }
+
+    fn parse(header: &str) -> Result<Maze, ParseMazeError> {
+        maze_file(header).parse()
+    }
+
+    #[test]
+    fn parses_the_header() {
+        let maze = parse(
+            "name: Test\nwall_color: ff0000\nportals: -2,3/30,3 -2,9/30,9\nfruit: 0,16 27,26",
+        )
+        .unwrap();
+
+        assert_eq!(maze.name, "Test");
+        assert_eq!(maze.wall_color, Color::RED);
+        assert_eq!(
+            maze.layout.portals,
+            [Portal::horizontal(3), Portal::horizontal(9)]
+        );
+        assert_eq!(
+            maze.fruit_entries,
+            [GridLocation { x: 0, y: 16 }, GridLocation { x: 27, y: 26 }]
+        );
+        assert_eq!(maze.artwork, None);
+    }
+
+    #[test]
+    fn parses_the_layout_bottom_row_last() {
+        let maze = parse("name: Test\nwall_color: ff0000").unwrap();
+
+        assert!(maze.layout.get(&GridLocation { x: 0, y: 0 }) == Ok(Some(Tile::Wall)));
+        assert!(maze.layout.get(&GridLocation { x: 1, y: 0 }) == Ok(None));
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        assert!(matches!(
+            parse("wall_color: ff0000"),
+            Err(ParseMazeError::MissingKey("name"))
+        ));
+        assert!(matches!(
+            parse("name: Test\nwall_color: ff0000\nghosts: 4"),
+            Err(ParseMazeError::UnknownKey(key)) if key == "ghosts"
+        ));
+        assert!(matches!(
+            parse("name: Test\nwall_color: red"),
+            Err(ParseMazeError::InvalidValue { key, .. }) if key == "wall_color"
+        ));
+        assert!(matches!(
+            parse("name: Test\nwall_color: ff0000\nfruit: 1;2"),
+            Err(ParseMazeError::InvalidValue { key, .. }) if key == "fruit"
+        ));
+        assert!(matches!(
+            parse("name Test"),
+            Err(ParseMazeError::InvalidLine(_))
+        ));
+        assert!(matches!(
+            "name: Test".parse::<Maze>(),
+            Err(ParseMazeError::MissingLayout)
+        ));
+    }
+
+    #[test]
+    fn rejects_bad_layouts() {
+        let short = "name: Test\nwall_color: ff0000\nlayout:\n#\n";
+
+        assert!(matches!(
+            short.parse::<Maze>(),
+            Err(ParseMazeError::Layout(ParseLayoutError::WrongHeight(1)))
+        ));
+    }
+
+    #[test]
+    fn saved_mazes_load_back_the_same() {
+        let bundled = [
+            Maze::classic(),
+            load(include_str!("../../assets/mazes/pinkys_parlour.maze")),
+            load(include_str!("../../assets/mazes/inkys_island.maze")),
+            load(include_str!("../../assets/mazes/clydes_canyon.maze")),
+        ];
+
+        for maze in bundled.into_iter().chain((0..5).map(generate)) {
+            let saved = maze.to_string();
+            let loaded: Maze = saved.parse().unwrap();
+            assert_eq!(loaded.to_string(), saved, "{}", maze.name);
+        }
+    }
+
+    #[test]
+    fn plays_each_maze_for_longer_then_alternates_the_last_two() {
+        let playlist = MazePlaylist::default();
+        let names: Vec<String> = (1..=21)
+            .map(|level| playlist.maze_for_level(level).name.clone())
+            .collect();
+
+        let expected = [
+            ["Classic"; 2].as_slice(),
+            &["Pinky's Parlour"; 3],
+            &["Inky's Island"; 4],
+            &["Clyde's Canyon"; 4],
+            &["Inky's Island"; 4],
+            &["Clyde's Canyon"; 4],
+        ]
+        .concat();
+        assert_eq!(names, expected);
+    }
+}
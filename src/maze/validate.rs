@@ -0,0 +1,190 @@
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+
+use crate::grid::GridLocation;
+use crate::layout::{ActorKind, Layout, Tile};
+use crate::level::{BOTTOM_MARGIN, HEIGHT_TILES, TOP_MARGIN, WIDTH_TILES};
+
+/// Something that makes a layout unplayable
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Problem {
+    MissingSpawn(Tile),
+    DuplicateSpawn(Tile, GridLocation),
+    NoGhosts,
+    NoFood,
+    UnreachableFood(GridLocation),
+    DeadEnd(GridLocation),
+    /// Pac-Man can wander out of the maze into the score display
+    Hole(GridLocation),
+}
+
+impl Problem {
+    pub fn location(&self) -> Option<GridLocation> {
+        match self {
+            Problem::MissingSpawn(_) | Problem::NoGhosts | Problem::NoFood => None,
+            Problem::DuplicateSpawn(_, loc)
+            | Problem::UnreachableFood(loc)
+            | Problem::DeadEnd(loc)
+            | Problem::Hole(loc) => Some(*loc),
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::MissingSpawn(tile) => write!(f, "no {}", spawn_name(*tile)),
+            Problem::DuplicateSpawn(tile, _) => write!(f, "too many {}", spawn_name(*tile)),
+            Problem::NoGhosts => write!(f, "no ghosts"),
+            Problem::NoFood => write!(f, "no dots"),
+            Problem::UnreachableFood(_) => write!(f, "dot out of reach"),
+            Problem::DeadEnd(_) => write!(f, "dead end"),
+            Problem::Hole(_) => write!(f, "hole in maze"),
+        }
+    }
+}
+
+fn spawn_name(tile: Tile) -> String {
+    match tile {
+        Tile::PacMan => "Pac-Man".to_string(),
+        Tile::Ghost(char) => format!("ghost {char}"),
+        _ => "spawn".to_string(),
+    }
+}
+
+/// Checks that a layout can be played, returning everything wrong with it
+pub fn validate(layout: &Layout) -> Vec<Problem> {
+    let mut problems = vec![];
+
+    let tiles: Vec<(GridLocation, Tile)> = (0..HEIGHT_TILES as isize)
+        .flat_map(|y| (0..WIDTH_TILES as isize).map(move |x| GridLocation { x, y }))
+        .filter_map(|loc| Some((loc, layout.get(&loc).ok().flatten()?)))
+        .collect();
+
+    if !tiles.iter().any(|(_, tile)| *tile == Tile::PacMan) {
+        problems.push(Problem::MissingSpawn(Tile::PacMan));
+    }
+
+    if !tiles.iter().any(|(_, tile)| matches!(tile, Tile::Ghost(_))) {
+        problems.push(Problem::NoGhosts);
+    }
+
+    // Several ghosts can start on the same spawn tile, so there's no need for more than one
+    let mut spawns = HashSet::new();
+    for (loc, tile) in &tiles {
+        if matches!(tile, Tile::PacMan | Tile::Ghost(_)) && !spawns.insert(*tile) {
+            problems.push(Problem::DuplicateSpawn(*tile, *loc));
+        }
+    }
+
+    let food: Vec<GridLocation> = tiles
+        .iter()
+        .filter(|(_, tile)| matches!(tile, Tile::Dot | Tile::Energizer))
+        .map(|(loc, _)| *loc)
+        .collect();
+
+    if food.is_empty() {
+        problems.push(Problem::NoFood);
+    }
+
+    let pac_man = match tiles.iter().find(|(_, tile)| *tile == Tile::PacMan) {
+        Some((loc, _)) => *loc,
+        None => return problems,
+    };
+
+    let reachable = reachable_from(layout, pac_man);
+
+    problems.extend(
+        food.into_iter()
+            .filter(|loc| !reachable.contains(loc))
+            .map(Problem::UnreachableFood),
+    );
+
+    let mut reachable: Vec<GridLocation> = reachable.into_iter().collect();
+    reachable.sort_by_key(|loc| (loc.y, loc.x));
+
+    // Vertical tunnels are allowed to pass through the score display
+    let tunnel_columns: HashSet<isize> = layout
+        .portals
+        .iter()
+        .flat_map(|portal| [portal.0, portal.1])
+        .filter(|portal| !(0..HEIGHT_TILES as isize).contains(&portal.y))
+        .map(|portal| portal.x)
+        .collect();
+
+    for loc in reachable {
+        let in_margin =
+            loc.y < BOTTOM_MARGIN as isize || loc.y >= (HEIGHT_TILES - TOP_MARGIN) as isize;
+
+        if in_margin && layout.in_bounds(&loc) && !tunnel_columns.contains(&loc.x) {
+            problems.push(Problem::Hole(loc));
+        } else if open_neighbours(layout, loc).count() < 2 {
+            problems.push(Problem::DeadEnd(loc));
+        }
+    }
+
+    problems
+}
+
+/// Neighbouring tiles Pac-Man can walk to, going through portals
+fn open_neighbours(layout: &Layout, loc: GridLocation) -> impl Iterator<Item = GridLocation> + '_ {
+    layout
+        .neighbours(loc)
+        .map(|(_, next)| next)
+        .filter(|next| layout.is_walkable_for(next, ActorKind::PacMan))
+}
+
+fn reachable_from(layout: &Layout, start: GridLocation) -> HashSet<GridLocation> {
+    let mut seen = HashSet::from([start]);
+    let mut queue = VecDeque::from([start]);
+
+    while let Some(loc) = queue.pop_front() {
+        for next in open_neighbours(layout, loc) {
+            if seen.insert(next) {
+                queue.push_back(next);
+            }
+        }
+    }
+
+    seen
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze::{generate, Maze};
+
+    #[test]
+    fn the_classic_maze_has_no_problems() {
+        assert_eq!(validate(&Maze::classic().layout), []);
+    }
+
+    #[test]
+    fn generated_mazes_have_no_problems() {
+        for seed in 0..20 {
+            assert_eq!(validate(&generate(seed).layout), [], "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn an_empty_layout_has_no_spawns_or_food() {
+        let problems = validate(&Layout::default());
+
+        assert!(problems.contains(&Problem::NoFood));
+        assert!(problems.contains(&Problem::NoGhosts));
+        assert!(problems.contains(&Problem::MissingSpawn(Tile::PacMan)));
+    }
+
+    #[test]
+    fn finds_a_second_spawn() {
+        let mut layout = Maze::classic().layout;
+        let dot = GridLocation { x: 1, y: 4 };
+        assert!(layout.get(&dot) == Ok(Some(Tile::Dot)));
+        layout.set(&dot, Some(Tile::PacMan));
+
+        assert!(matches!(
+            validate(&layout)[..],
+            [Problem::DuplicateSpawn(Tile::PacMan, _)]
+        ));
+    }
+}
@@ -0,0 +1,738 @@
+//! Two players on different machines playing the same game, peer to peer over UDP, with rollback
+//! netcode.
+//!
+//! Each side plays on straight away with a guess at what the other player is doing, which is that
+//! they're still doing whatever they did last. When the real input arrives and the guess was
+//! wrong, the game is put back how it was on that frame with a [`Snapshot`], and played forward
+//! again with what really happened, before the window is drawn again.
+//!
+//! ```text
+//! GHOST_PLAYER=blinky pac-man netplay --bind 192.168.1.10:7000 --peer 192.168.1.11:7000 --player 0
+//! GHOST_PLAYER=blinky pac-man netplay --bind 192.168.1.11:7000 --peer 192.168.1.10:7000 --player 1
+//! ```
+//!
+//! Player 0 is the first Pac-Man, and player 1 is either the second Pac-Man with `PAC_MEN=2`, or
+//! the ghost chosen with `GHOST_PLAYER`. Both sides need the same settings and seed. Each player
+//! steers with the first player's keys or gamepad on their own machine, and everything that steers
+//! the game goes through [`SyncedInput`], so both machines play exactly the same frames.
+//!
+//! With `--headless` the game runs without a window, like [`gym`](crate::gym), and a seeded bot
+//! plays instead of the keyboard, so two processes can play a whole game against each other
+//! unattended. Once the `--frames` are up, each prints a line of JSON with a checksum of the final
+//! frame, which should match, as the last line after its log messages. The sides also swap
+//! checksums every second while they play, and log any frame that differs.
+
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::Duration;
+
+use bevy::app::{AppExit, ScheduleRunnerSettings};
+use bevy::ecs::schedule::ShouldRun;
+use bevy::input::InputSystem;
+use bevy::log::LogPlugin;
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use bevy::utils::Instant;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::actor::autopilot::Autopilot;
+use crate::actor::movement::Dir;
+use crate::actor::player::{DirInput, PlayerNumber, SyncedInput};
+use crate::from_env::FromEnv;
+use crate::gym;
+use crate::players::{GhostPlayer, PacMen};
+use crate::random::GameRng;
+use crate::simulate::{parse_value, ParseError};
+use crate::snapshot::Snapshot;
+
+/// How long each frame of the game lasts
+const FRAME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// How many frames can be played on a guess before waiting for the other side to catch up.
+/// Each one may have to be played again, so this is also how much work a rollback can be.
+const MAX_ROLLBACK: u32 = 8;
+
+/// How often the sides check they're still playing the same game
+const CHECKSUM_EVERY: u32 = 60;
+
+/// Gives up if nothing arrives from the other side for this long
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long to wait for the other side to turn up
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often to say hello while waiting for the other side
+const HELLO_EVERY: Duration = Duration::from_millis(100);
+
+/// How long to keep answering after the game's over, in case the other side is still missing
+/// something
+const LINGER: Duration = Duration::from_millis(500);
+
+/// The most inputs sent in one packet, which is plenty as neither side gets far ahead
+const MAX_INPUTS_PER_MESSAGE: usize = 64;
+
+/// Plays the game against the other end of a [`Session`], which has to be inserted first.
+///
+/// This takes over the whole schedule, which runs once for every frame of the game instead of once
+/// for every time the window is drawn: not at all while waiting for the other side, and several
+/// times over after a rollback.
+pub struct NetplayPlugin;
+
+impl Plugin for NetplayPlugin {
+    fn build(&self, app: &mut App) {
+        // Start the clock, so the level is put together without anything moving and the first
+        // frame lasts as long as the rest
+        let mut time = app.world.resource_mut::<Time>();
+        let startup = time.startup();
+        time.update_with_instant(startup);
+
+        let mut session = app.world.resource_mut::<Session>();
+        session.clock = startup;
+        let seed = session.options.seed;
+
+        app.insert_resource(GameRng::seeded(seed))
+            .insert_resource(SyncedInput(vec![None; 2]))
+            .insert_resource(TimeUpdateStrategy::ManualInstant(startup))
+            // Only the players steer
+            .insert_resource(Autopilot::Off)
+            .add_system_to_stage(CoreStage::PreUpdate, read_local_input.after(InputSystem))
+            .add_system_to_stage(CoreStage::First, start_frame.at_start())
+            .add_system_to_stage(CoreStage::Last, end_frame.at_end());
+        app.schedule.set_run_criteria(play_frames);
+    }
+}
+
+/// The game without a window, with a bot playing this side, which carries on until the game's
+/// over
+pub fn headless_app(session: Session) -> App {
+    let mut app = gym::headless_app();
+
+    app.insert_resource(ScheduleRunnerSettings::run_loop(Duration::from_millis(1)))
+        // Otherwise there's no way of hearing from the game without a window
+        .add_plugin(LogPlugin {
+            filter: "warn,pac_man::netplay=info".to_string(),
+            ..default()
+        })
+        .insert_resource(session)
+        .add_plugin(NetplayPlugin);
+
+    app
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Options {
+    pub bind: SocketAddr,
+    pub peer: SocketAddr,
+    /// Which player this side is
+    pub player: usize,
+    pub seed: u64,
+    /// How long the game lasts, or `None` to play until the window's closed
+    pub frames: Option<u32>,
+    /// How many frames late this side's input is used, so it has time to get to the other side
+    /// and fewer guesses turn out wrong
+    pub delay: u32,
+    /// The fraction of packets to throw away instead of sending, to try out a bad connection
+    pub loss: f64,
+    /// Play without a window, with a bot instead of the keyboard
+    pub headless: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            bind: SocketAddr::from(([127, 0, 0, 1], 7000)),
+            peer: SocketAddr::from(([127, 0, 0, 1], 7001)),
+            player: 0,
+            seed: 0,
+            frames: None,
+            delay: 2,
+            loss: 0.0,
+            headless: false,
+        }
+    }
+}
+
+impl Options {
+    /// Reads options from arguments like `--player 1 --peer 127.0.0.1:7000`
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, ParseError> {
+        let mut options = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(flag) = args.next() {
+            if flag == "--headless" {
+                options.headless = true;
+                continue;
+            }
+
+            let value = args
+                .next()
+                .ok_or_else(|| ParseError(format!("missing value for {flag}")))?;
+            match flag.as_str() {
+                "--bind" => options.bind = parse_value(&flag, &value)?,
+                "--peer" => options.peer = parse_value(&flag, &value)?,
+                "--player" => options.player = parse_value(&flag, &value)?,
+                "--seed" => options.seed = parse_value(&flag, &value)?,
+                "--frames" => options.frames = Some(parse_value(&flag, &value)?),
+                "--delay" => options.delay = parse_value(&flag, &value)?,
+                "--loss" => options.loss = parse_value(&flag, &value)?,
+                _ => return Err(ParseError(format!("unknown option {flag}"))),
+            }
+        }
+
+        if options.player > 1 {
+            return Err(ParseError("the player must be 0 or 1".to_string()));
+        }
+        // There's no window to close, so a bot game has to end some time
+        if options.headless && options.frames.is_none() {
+            options.frames = Some(60 * 60);
+        }
+        Ok(options)
+    }
+}
+
+/// How a game went, from one side
+#[derive(Serialize, Clone, Debug)]
+pub struct Summary {
+    pub player: usize,
+    pub frames: u32,
+    /// Of the game after the last frame, which should be the same on both sides
+    pub checksum: u64,
+    pub score: u32,
+    /// How many times a wrong guess meant going back
+    pub rollbacks: u32,
+    /// How many frames were played again because of them
+    pub replayed_frames: u32,
+    /// How many checksums differed from the other side's
+    pub desyncs: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Message {
+    /// Sent until the other side answers, to check both are playing the same game
+    Hello { seed: u64, player: usize },
+    /// The sender's inputs from the frame `start` on, and how many of ours it's got
+    Inputs {
+        start: u32,
+        dirs: Vec<Option<Dir>>,
+        received: u32,
+    },
+    /// How the game was after a frame that both sides have all the inputs for
+    Checksum { frame: u32, checksum: u64 },
+}
+
+/// Stands in for a player, holding a random direction for a random time
+struct Bot {
+    rng: StdRng,
+    dir: Option<Dir>,
+    frames_left: u32,
+}
+
+impl Bot {
+    fn new(seed: u64, player: usize) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed.wrapping_mul(31).wrapping_add(player as u64)),
+            dir: None,
+            frames_left: 0,
+        }
+    }
+
+    fn next(&mut self) -> Option<Dir> {
+        if self.frames_left == 0 {
+            self.frames_left = self.rng.gen_range(10..90);
+            self.dir = match self.rng.gen_range(0..5) {
+                0 => Some(Dir::Left),
+                1 => Some(Dir::Right),
+                2 => Some(Dir::Down),
+                3 => Some(Dir::Up),
+                _ => None,
+            };
+        }
+        self.frames_left -= 1;
+        self.dir
+    }
+}
+
+/// This side of a game, and everything it knows about the other
+#[derive(Resource)]
+pub struct Session {
+    options: Options,
+    socket: UdpSocket,
+    /// For throwing packets away
+    rng: StdRng,
+    /// Plays this side instead of the keyboard, without a window
+    bot: Option<Bot>,
+    /// The direction this side's player is holding now
+    held: Option<Dir>,
+    connected: bool,
+    /// Whether the level's been put together, which takes a run of the schedule before the first
+    /// frame so that going back to the first frame doesn't have to do it again
+    set_up: bool,
+    created: Instant,
+    last_hello: Option<Instant>,
+    /// This side's input for each frame, known `delay` frames ahead
+    local: Vec<Option<Dir>>,
+    /// The other side's input for each frame, as far as it's arrived
+    remote: Vec<Option<Dir>>,
+    /// What was guessed for the other side on each frame played so far
+    guessed: Vec<Option<Dir>>,
+    /// The game before each frame that might have to be played again
+    snapshots: BTreeMap<u32, Snapshot>,
+    /// The frame to put the game back to before playing the next one, after a wrong guess
+    restore: Option<u32>,
+    /// How many frames have been played
+    frame: u32,
+    /// The furthest the game has got, which it catches up to again after a rollback
+    furthest: u32,
+    next_frame_at: Instant,
+    /// The time in the game, which moves on by exactly a frame every frame, however late it's
+    /// played
+    clock: Instant,
+    /// Whether the schedule has finished running for now, so the window can be drawn
+    between_frames: bool,
+    /// How many of our inputs the other side has got
+    acked: u32,
+    /// Checksums of frames that might still be played again
+    unconfirmed: BTreeMap<u32, u64>,
+    /// Checksums of frames from both sides, until they can be compared
+    ours: HashMap<u32, u64>,
+    theirs: HashMap<u32, u64>,
+    last_checked: u32,
+    last_heard: Instant,
+    /// When the last frame was confirmed
+    finished: Option<Instant>,
+    rollbacks: u32,
+    replayed_frames: u32,
+    desyncs: u32,
+}
+
+impl Session {
+    /// Gets ready to play, without waiting for the other side yet
+    pub fn bind(options: &Options) -> Result<Self, Box<dyn Error>> {
+        let players = *PacMen::from_env() + usize::from(GhostPlayer::from_env().is_some());
+        if players != 2 {
+            return Err("netplay needs two players: set PAC_MEN=2 or GHOST_PLAYER".into());
+        }
+
+        let socket = UdpSocket::bind(options.bind)?;
+        socket.connect(options.peer)?;
+        socket.set_nonblocking(true)?;
+
+        let now = Instant::now();
+        Ok(Self {
+            options: *options,
+            socket,
+            rng: StdRng::seed_from_u64(options.seed ^ options.player as u64),
+            bot: options
+                .headless
+                .then(|| Bot::new(options.seed, options.player)),
+            held: None,
+            connected: false,
+            set_up: false,
+            created: now,
+            last_hello: None,
+            local: vec![None; options.delay as usize],
+            remote: Vec::new(),
+            guessed: Vec::new(),
+            snapshots: BTreeMap::new(),
+            restore: None,
+            frame: 0,
+            furthest: 0,
+            next_frame_at: now,
+            clock: now,
+            between_frames: true,
+            acked: 0,
+            unconfirmed: BTreeMap::new(),
+            ours: HashMap::new(),
+            theirs: HashMap::new(),
+            last_checked: 0,
+            last_heard: now,
+            finished: None,
+            rollbacks: 0,
+            replayed_frames: 0,
+            desyncs: 0,
+        })
+    }
+
+    fn frames(&self) -> u32 {
+        self.options.frames.unwrap_or(u32::MAX)
+    }
+
+    /// Catches up with the other side, and works out whether the game's over
+    fn exchange(&mut self) -> Result<bool, Box<dyn Error>> {
+        if self.connected {
+            self.read_ahead();
+            self.send_inputs()?;
+        } else {
+            self.say_hello()?;
+        }
+
+        while let Some(message) = self.receive()? {
+            self.handle(message)?;
+        }
+
+        if !self.connected {
+            if self.created.elapsed() > CONNECT_TIMEOUT {
+                return Err("nobody answered".into());
+            }
+            return Ok(true);
+        }
+        if self.last_heard.elapsed() > TIMEOUT {
+            return Err("lost the connection".into());
+        }
+
+        self.roll_back_if_wrong();
+        self.exchange_checksums()?;
+        self.keep_playing()
+    }
+
+    fn say_hello(&mut self) -> io::Result<()> {
+        match self.last_hello {
+            Some(at) if at.elapsed() < HELLO_EVERY => return Ok(()),
+            Some(_) => {}
+            None => info!("Waiting for {}", self.options.peer),
+        }
+
+        self.last_hello = Some(Instant::now());
+        self.send(&self.hello())
+    }
+
+    fn hello(&self) -> Message {
+        Message::Hello {
+            seed: self.options.seed,
+            player: self.options.player,
+        }
+    }
+
+    /// Whether there's another frame to play before the window's drawn again
+    fn next_frame(&mut self) -> bool {
+        if !self.connected {
+            return false;
+        }
+
+        // Catching up again after a rollback
+        if self.frame < self.furthest {
+            self.replayed_frames += 1;
+            return true;
+        }
+
+        // Carry on in real time, unless that means guessing too far ahead
+        let now = Instant::now();
+        if now < self.next_frame_at
+            || self.frame >= self.frames()
+            || self.frame >= self.remote.len() as u32 + MAX_ROLLBACK
+        {
+            return false;
+        }
+
+        self.read_ahead();
+        self.furthest = self.frame + 1;
+        // Don't rush to catch up after waiting
+        self.next_frame_at = (self.next_frame_at + FRAME).max(now - FRAME * 2);
+        true
+    }
+
+    /// Decides this side's input up to `delay` frames after the next one
+    fn read_ahead(&mut self) {
+        let until = (self.frame + self.options.delay + 1).min(self.frames());
+        while self.local.len() < until as usize {
+            let dir = match &mut self.bot {
+                Some(bot) => bot.next(),
+                None => self.held,
+            };
+            self.local.push(dir);
+        }
+    }
+
+    /// Everyone's input for a frame, guessing the other side's if it hasn't arrived yet
+    fn inputs(&mut self, frame: u32) -> Vec<Option<Dir>> {
+        let frame = frame as usize;
+        let remote = match self.remote.get(frame) {
+            Some(dir) => *dir,
+            None => self.remote.last().copied().flatten(),
+        };
+        self.guessed.truncate(frame);
+        self.guessed.push(remote);
+
+        let mut inputs = vec![None; 2];
+        inputs[self.options.player] = self.local[frame];
+        inputs[1 - self.options.player] = remote;
+        inputs
+    }
+
+    /// Keeps answering for a moment after the last frame, in case the other side is still
+    /// missing something, then sums the game up
+    fn keep_playing(&mut self) -> Result<bool, Box<dyn Error>> {
+        let frames = match self.options.frames {
+            Some(frames) => frames,
+            None => return Ok(true),
+        };
+        if self.confirmed() < frames || self.acked < frames {
+            return Ok(true);
+        }
+
+        let finished = *self.finished.get_or_insert_with(Instant::now);
+        if let Some(checksum) = self.ours.get(&frames) {
+            self.send(&Message::Checksum {
+                frame: frames,
+                checksum: *checksum,
+            })?;
+        }
+        self.compare_checksums();
+        if finished.elapsed() < LINGER {
+            return Ok(true);
+        }
+
+        let snapshot = &self.snapshots[&frames];
+        let summary = Summary {
+            player: self.options.player,
+            frames,
+            checksum: snapshot.checksum(),
+            score: snapshot.score(),
+            rollbacks: self.rollbacks,
+            replayed_frames: self.replayed_frames,
+            desyncs: self.desyncs,
+        };
+        println!("{}", serde_json::to_string(&summary)?);
+        Ok(false)
+    }
+
+    /// How many frames have been played with everyone's real input, which can't change any more
+    fn confirmed(&self) -> u32 {
+        self.frame.min(self.remote.len() as u32)
+    }
+
+    /// Goes back to the first frame where the guess at the other side's input was wrong
+    fn roll_back_if_wrong(&mut self) {
+        let known = self.confirmed();
+        let wrong = (self.last_checked..known)
+            .find(|frame| self.guessed[*frame as usize] != self.remote[*frame as usize]);
+        self.last_checked = known;
+
+        if let Some(frame) = wrong {
+            self.restore = Some(frame);
+            self.frame = frame;
+            self.unconfirmed
+                .retain(|checksummed, _| *checksummed <= frame);
+            self.rollbacks += 1;
+        }
+
+        // Nothing before the confirmed frame can be wrong any more
+        let confirmed = self.confirmed();
+        self.snapshots = self.snapshots.split_off(&confirmed);
+    }
+
+    fn exchange_checksums(&mut self) -> io::Result<()> {
+        let later = self.unconfirmed.split_off(&(self.confirmed() + 1));
+        let confirmed = std::mem::replace(&mut self.unconfirmed, later);
+
+        for (frame, checksum) in confirmed {
+            self.ours.insert(frame, checksum);
+            self.send(&Message::Checksum { frame, checksum })?;
+        }
+        self.compare_checksums();
+        Ok(())
+    }
+
+    fn compare_checksums(&mut self) {
+        let frames: Vec<u32> = self
+            .theirs
+            .keys()
+            .filter(|frame| self.ours.contains_key(frame))
+            .copied()
+            .collect();
+
+        for frame in frames {
+            let theirs = self.theirs.remove(&frame);
+            if self.ours.get(&frame) != theirs.as_ref() {
+                warn!("Out of sync with the other side after frame {frame}");
+                self.desyncs += 1;
+            }
+        }
+    }
+
+    fn send_inputs(&mut self) -> io::Result<()> {
+        let start = (self.acked as usize).min(self.local.len());
+        let end = self.local.len().min(start + MAX_INPUTS_PER_MESSAGE);
+        self.send(&Message::Inputs {
+            start: start as u32,
+            dirs: self.local[start..end].to_vec(),
+            received: self.remote.len() as u32,
+        })
+    }
+
+    fn handle(&mut self, message: Message) -> Result<(), Box<dyn Error>> {
+        // Whatever it is, the other side must have heard our hello
+        if !self.connected {
+            info!("Connected to {}", self.options.peer);
+            self.connected = true;
+            self.next_frame_at = Instant::now();
+        }
+
+        match message {
+            Message::Hello { seed, player } => {
+                if seed != self.options.seed {
+                    return Err(format!("the other side is using seed {seed}").into());
+                }
+                if player == self.options.player {
+                    return Err(format!("the other side is player {player} too").into());
+                }
+            }
+            Message::Inputs {
+                start,
+                dirs,
+                received,
+            } => {
+                self.acked = self.acked.max(received);
+                // Only what carries on from what's already here
+                let have = self.remote.len();
+                let start = start as usize;
+                if start <= have {
+                    self.remote.extend(dirs.into_iter().skip(have - start));
+                }
+            }
+            Message::Checksum { frame, checksum } => {
+                self.theirs.insert(frame, checksum);
+            }
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, message: &Message) -> io::Result<()> {
+        if self.rng.gen_bool(self.options.loss.clamp(0.0, 1.0)) {
+            return Ok(());
+        }
+
+        let bytes = serde_json::to_vec(message)?;
+        match self.socket.send(&bytes) {
+            // Nobody's listening yet
+            Err(err) if is_nothing_yet(&err) => Ok(()),
+            result => result.map(|_| ()),
+        }
+    }
+
+    fn receive(&mut self) -> io::Result<Option<Message>> {
+        let mut buffer = [0; 4096];
+        loop {
+            match self.socket.recv(&mut buffer) {
+                Ok(len) => {
+                    self.last_heard = Instant::now();
+                    match serde_json::from_slice(&buffer[..len]) {
+                        Ok(message) => return Ok(Some(message)),
+                        // Not for us
+                        Err(_) => continue,
+                    }
+                }
+                Err(err) if is_nothing_yet(&err) => return Ok(None),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+fn is_nothing_yet(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut | io::ErrorKind::ConnectionRefused
+    )
+}
+
+/// Decides before each run of the schedule whether it should play another frame. The first time
+/// after the window's been drawn, it also catches up with the other side.
+fn play_frames(
+    mut session: ResMut<Session>,
+    mut time: ResMut<TimeUpdateStrategy>,
+    mut exit: EventWriter<AppExit>,
+) -> ShouldRun {
+    if !session.set_up {
+        return ShouldRun::YesAndCheckAgain;
+    }
+
+    if session.between_frames {
+        session.between_frames = false;
+
+        match session.exchange() {
+            Ok(true) => {}
+            Ok(false) => {
+                exit.send(AppExit);
+                return ShouldRun::No;
+            }
+            Err(err) => {
+                error!("Stopped playing {}: {err}", session.options.peer);
+                exit.send(AppExit);
+                return ShouldRun::No;
+            }
+        }
+    }
+
+    if session.next_frame() {
+        session.clock += FRAME;
+        *time = TimeUpdateStrategy::ManualInstant(session.clock);
+        ShouldRun::YesAndCheckAgain
+    } else {
+        session.between_frames = true;
+        ShouldRun::No
+    }
+}
+
+/// Steers the game with everyone's input for the frame that's about to be played, after going
+/// back to an earlier frame if a guess was wrong
+fn start_frame(world: &mut World) {
+    world.resource_scope(|world, mut session: Mut<Session>| {
+        if !session.set_up {
+            return;
+        }
+
+        if let Some(frame) = session.restore.take() {
+            if let Err(err) = session.snapshots[&frame].restore(world) {
+                error!("Can't go back to frame {frame}: {err}");
+                world.send_event(AppExit);
+            }
+        }
+
+        let frame = session.frame;
+        session
+            .snapshots
+            .entry(frame)
+            .or_insert_with(|| Snapshot::take(world));
+
+        let inputs = session.inputs(frame);
+        world.insert_resource(SyncedInput(inputs));
+    });
+}
+
+/// Keeps a copy of the game after each frame, in case it has to be played again
+fn end_frame(world: &mut World) {
+    let mut session = world.resource_mut::<Session>();
+    if !session.set_up {
+        session.set_up = true;
+        return;
+    }
+
+    let snapshot = Snapshot::take(world);
+    let mut session = world.resource_mut::<Session>();
+    session.frame += 1;
+    let frame = session.frame;
+    if frame.checked_rem(CHECKSUM_EVERY) == Some(0) || Some(frame) == session.options.frames {
+        session.unconfirmed.insert(frame, snapshot.checksum());
+    }
+    session.snapshots.insert(frame, snapshot);
+}
+
+/// Follows what this side's player is holding, with the first player's controls, so the most
+/// recently pressed direction wins like it does when playing alone
+fn read_local_input(input: DirInput, mut session: ResMut<Session>, mut held: Local<Vec<Dir>>) {
+    let player = PlayerNumber(0);
+    held.retain(|dir| input.pressed_here(player, *dir));
+    for dir in [Dir::Left, Dir::Right, Dir::Down, Dir::Up] {
+        if input.pressed_here(player, dir) && !held.contains(&dir) {
+            held.push(dir);
+        }
+    }
+
+    session.held = held.last().copied();
+}
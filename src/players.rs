@@ -0,0 +1,224 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::actor::ghost::{Ghost, Personality};
+use crate::actor::player::{Lives, PlayerDied, UpdateLives};
+use crate::food::Food;
+use crate::from_env::FromEnv;
+use crate::grid::GridLocation;
+use crate::layout::{Layout, Tile};
+use crate::level::{CurrentLevel, RestartLevel, StartLevel, HEIGHT_TILES, WIDTH_TILES};
+use crate::maze::{CurrentMaze, Maze, MazePlaylist};
+use crate::score::Score;
+
+pub struct PlayersPlugin;
+
+impl Plugin for PlayersPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Players::from_env())
+            .insert_resource(PacMen::from_env())
+            .insert_resource(GhostPlayer::from_env())
+            .add_system(
+                switch_players_on_death
+                    .label(SwitchPlayers)
+                    .after(UpdateLives)
+                    // Restart the level for the next player on the frame after, once everything
+                    // has finished reacting to the death
+                    .after(StartLevel),
+            );
+    }
+}
+
+#[derive(SystemLabel)]
+pub struct SwitchPlayers;
+
+/// Who's playing, for games where players take turns like the arcade's 2UP mode.
+/// Each player has their own score, lives, level and dots, which are swapped in whenever
+/// the player whose turn it is dies.
+#[derive(Resource, Clone, Serialize, Deserialize)]
+pub struct Players {
+    current: usize,
+    /// Everyone waiting for their turn, or `None` for players who haven't had one yet
+    waiting: Vec<Option<PlayerState>>,
+}
+
+impl Default for Players {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+impl Players {
+    pub fn new(count: usize) -> Self {
+        let mut waiting = Vec::new();
+        waiting.resize_with(count.max(1), || None);
+        Self {
+            current: 0,
+            waiting,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.waiting.len()
+    }
+
+    /// Whose turn it is, starting from 0
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// A player's score, which for the current player is in [`Score`]
+    pub fn score(&self, player: usize, current_score: &Score) -> u32 {
+        if player == self.current {
+            return **current_score;
+        }
+
+        match self.waiting.get(player) {
+            Some(Some(state)) => *state.score,
+            _ => 0,
+        }
+    }
+
+    /// The next player after the current one who still has lives left
+    fn next_player(&self) -> Option<usize> {
+        (1..self.count())
+            .map(|offset| (self.current + offset) % self.count())
+            .find(|player| match &self.waiting[*player] {
+                Some(state) => *state.lives > 0,
+                None => true,
+            })
+    }
+}
+
+impl FromEnv for Players {
+    fn with_env_overrides(self) -> Self {
+        get_players().map(Self::new).unwrap_or(self)
+    }
+}
+
+/// How many players take turns
+fn get_players() -> Option<usize> {
+    std::env::var("PLAYERS").ok()?.parse().ok()
+}
+
+/// How many Pac-Men play on the board at the same time, each with their own controls and score.
+/// They share their lives, so the level restarts for everyone when any of them is caught.
+#[derive(Resource, Copy, Clone, Debug, Deref)]
+pub struct PacMen(pub usize);
+
+impl Default for PacMen {
+    fn default() -> Self {
+        Self(1)
+    }
+}
+
+impl FromEnv for PacMen {
+    fn with_env_overrides(self) -> Self {
+        get_pac_men()
+            .map(|count| Self(count.max(1)))
+            .unwrap_or(self)
+    }
+}
+
+fn get_pac_men() -> Option<usize> {
+    std::env::var("PAC_MEN").ok()?.parse().ok()
+}
+
+/// The ghost with this personality that a human plays as, if there is one.
+/// The first ghost in the roster with it is [`Possessed`](crate::actor::ghost::Possessed).
+#[derive(Resource, Copy, Clone, Default, Debug, Deref)]
+pub struct GhostPlayer(pub Option<Personality>);
+
+impl FromEnv for GhostPlayer {
+    fn with_env_overrides(self) -> Self {
+        match get_ghost_player() {
+            Some(personality) => Self(Some(personality)),
+            None => self,
+        }
+    }
+}
+
+/// The personality of the ghost to play as, e.g. `GHOST_PLAYER=blinky`
+fn get_ghost_player() -> Option<Personality> {
+    std::env::var("GHOST_PLAYER").ok()?.parse().ok()
+}
+
+/// Everything belonging to a player while they wait for their turn
+#[derive(Clone, Serialize, Deserialize)]
+struct PlayerState {
+    score: Score,
+    lives: Lives,
+    level: CurrentLevel,
+    /// The maze they were playing, without the dots they've eaten
+    #[serde(with = "crate::snapshot::as_text")]
+    maze: Maze,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn switch_players_on_death(
+    mut died_events: EventReader<PlayerDied>,
+    mut restart_events: EventWriter<RestartLevel>,
+    mut players: ResMut<Players>,
+    mut score: ResMut<Score>,
+    mut lives: ResMut<Lives>,
+    mut level: ResMut<CurrentLevel>,
+    mut layout: ResMut<Layout>,
+    mut current_maze: ResMut<CurrentMaze>,
+    playlist: Res<MazePlaylist>,
+    food: Query<&GridLocation, (With<Food>, Without<Ghost>)>,
+) {
+    if died_events.iter().count() == 0 {
+        return;
+    }
+
+    let next = match players.next_player() {
+        Some(next) => next,
+        None => return,
+    };
+
+    let remaining: Vec<GridLocation> = food.iter().copied().collect();
+    let mut maze = current_maze.0.clone();
+    maze.layout = without_eaten_food(&layout, &remaining);
+
+    let current = players.current;
+    let next_state = players.waiting[next].take();
+    players.waiting[current] = Some(PlayerState {
+        score: std::mem::take(&mut *score),
+        lives: std::mem::take(&mut *lives),
+        level: std::mem::take(&mut *level),
+        maze,
+    });
+    players.current = next;
+
+    let maze = match next_state {
+        Some(state) => {
+            *score = state.score;
+            *lives = state.lives;
+            *level = state.level;
+            state.maze
+        }
+        None => playlist.maze_for_level(**level),
+    };
+
+    info!("Player {}'s turn on {}", next + 1, maze.name);
+    *layout = maze.layout.clone();
+    *current_maze = CurrentMaze(maze);
+    restart_events.send(RestartLevel);
+}
+
+/// The layout with the dots and energizers that have been eaten taken out
+fn without_eaten_food(layout: &Layout, remaining: &[GridLocation]) -> Layout {
+    let mut layout = layout.clone();
+
+    for x in 0..WIDTH_TILES as isize {
+        for y in 0..HEIGHT_TILES as isize {
+            let loc = GridLocation { x, y };
+            let is_food = matches!(layout.get(&loc), Ok(Some(Tile::Dot | Tile::Energizer)));
+            if is_food && !remaining.contains(&loc) {
+                layout.set(&loc, None);
+            }
+        }
+    }
+
+    layout
+}
@@ -1,4 +1,5 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::fmt::Formatter;
 use std::ops::AddAssign;
@@ -8,6 +9,7 @@ pub struct ScorePlugin;
 impl Plugin for ScorePlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(Score(0))
+            .init_resource::<PlayerScores>()
             .insert_resource(HighScore(0))
             .add_system(update_high_score.label(UpdateHighScore).after(UpdateScore));
     }
@@ -19,7 +21,7 @@ pub struct UpdateScore;
 #[derive(SystemLabel)]
 pub struct UpdateHighScore;
 
-#[derive(Resource)]
+#[derive(Resource, Default, Clone, Debug, Deref, Serialize, Deserialize)]
 pub struct Score(u32);
 
 impl fmt::Display for Score {
@@ -34,9 +36,32 @@ impl AddAssign<u32> for Score {
     }
 }
 
-#[derive(Resource)]
+/// What each of the Pac-Men on the board has scored by themselves, which adds up to [`Score`]
+#[derive(Resource, Default, Clone, Debug, Serialize, Deserialize)]
+pub struct PlayerScores(Vec<u32>);
+
+impl PlayerScores {
+    pub fn get(&self, player: usize) -> u32 {
+        self.0.get(player).copied().unwrap_or_default()
+    }
+
+    pub fn add(&mut self, player: usize, points: u32) {
+        if self.0.len() <= player {
+            self.0.resize(player + 1, 0);
+        }
+        self.0[player] += points;
+    }
+}
+
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
 pub struct HighScore(u32);
 
+impl HighScore {
+    pub fn new(score: u32) -> Self {
+        Self(score)
+    }
+}
+
 impl fmt::Display for HighScore {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         self.0.fmt(f)
@@ -0,0 +1,213 @@
+//! Plays lots of seeded games headlessly with one of the autopilot bots, and sums up how they went,
+//! to see how changes to the ghosts affect the balance of the game.
+//!
+//! ```text
+//! pac-man simulate --games 100 --seed 0 --bot cautious --format csv
+//! ```
+//!
+//! The ghosts are set up the same way as in the game, e.g. with `GHOST_NAVIGATION`,
+//! `GHOST_TARGETING`, `GHOST_WRAPPED_DISTANCE` and `GHOSTS`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::actor::autopilot::Autopilot;
+use crate::actor::ghost::Personality;
+use crate::gym::PacManEnv;
+
+/// How many frames the game runs per second
+const FPS: f32 = 60.0;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Options {
+    pub games: u64,
+    /// The first game uses this seed, and each game after it the next one
+    pub seed: u64,
+    pub bot: Autopilot,
+    pub format: Format,
+    /// Games that are still going after this many steps are stopped where they are
+    pub max_steps: u32,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            games: 100,
+            seed: 0,
+            bot: Autopilot::Cautious,
+            format: Format::Csv,
+            max_steps: 10_000,
+        }
+    }
+}
+
+impl Options {
+    /// Reads options from arguments like `--games 10 --bot greedy`
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, ParseError> {
+        let mut options = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(flag) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| ParseError(format!("missing value for {flag}")))?;
+            match flag.as_str() {
+                "--games" => options.games = parse_value(&flag, &value)?,
+                "--seed" => options.seed = parse_value(&flag, &value)?,
+                "--max-steps" => options.max_steps = parse_value(&flag, &value)?,
+                "--format" => options.format = parse_value(&flag, &value)?,
+                "--bot" => {
+                    options.bot = match value.to_lowercase().as_str() {
+                        "greedy" => Autopilot::Greedy,
+                        "cautious" => Autopilot::Cautious,
+                        _ => return Err(ParseError(format!("unknown bot {value}"))),
+                    }
+                }
+                _ => return Err(ParseError(format!("unknown option {flag}"))),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+pub(crate) fn parse_value<T: FromStr>(flag: &str, value: &str) -> Result<T, ParseError> {
+    value
+        .parse()
+        .map_err(|_| ParseError(format!("invalid value for {flag}: {value}")))
+}
+
+#[derive(Debug)]
+pub struct ParseError(pub(crate) String);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+/// How all the games went, added up
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct Stats {
+    pub games: u64,
+    pub mean_score: f32,
+    pub mean_levels_cleared: f32,
+    /// How many times each kind of ghost caught Pac-Man, over all the games
+    pub deaths_by_ghost: HashMap<Personality, u32>,
+    /// The mean number of seconds per game that the ghosts were frightened for
+    pub mean_frightened_seconds: f32,
+    /// The mean number of dots and energizers left in the maze each time Pac-Man died
+    pub mean_dots_remaining_at_death: f32,
+}
+
+/// Plays all the games and sums them up
+pub fn run(options: &Options) -> Stats {
+    let mut total_score = 0;
+    let mut total_levels_cleared = 0;
+    let mut total_frightened_frames = 0;
+    let mut deaths = 0;
+    let mut total_dots_at_death = 0;
+    let mut deaths_by_ghost: HashMap<Personality, u32> =
+        Personality::ALL.iter().map(|p| (*p, 0)).collect();
+
+    for game in 0..options.games {
+        let mut env = PacManEnv::new(options.seed.wrapping_add(game));
+        env.set_autopilot(options.bot);
+        let mut score = 0;
+
+        for _ in 0..options.max_steps {
+            let step = env.step(None);
+            let info = &step.info;
+
+            score = info.score;
+            total_frightened_frames += info.frightened_frames as u64;
+            if info.cleared {
+                total_levels_cleared += 1;
+            }
+            if info.died {
+                deaths += 1;
+                total_dots_at_death += step.observation.dots_remaining;
+            }
+            if let Some(ghost) = info.killed_by {
+                *deaths_by_ghost.entry(ghost).or_default() += 1;
+            }
+
+            if step.done {
+                break;
+            }
+        }
+
+        total_score += score as u64;
+    }
+
+    let mean = |total: f32, count: u64| {
+        if count == 0 {
+            0.0
+        } else {
+            total / count as f32
+        }
+    };
+
+    Stats {
+        games: options.games,
+        mean_score: mean(total_score as f32, options.games),
+        mean_levels_cleared: mean(total_levels_cleared as f32, options.games),
+        deaths_by_ghost,
+        mean_frightened_seconds: mean(total_frightened_frames as f32 / FPS, options.games),
+        mean_dots_remaining_at_death: mean(total_dots_at_death as f32, deaths),
+    }
+}
+
+/// Writes the stats as a CSV header and row, or as a JSON object
+pub fn write_stats(stats: &Stats, format: Format, mut output: impl Write) -> io::Result<()> {
+    match format {
+        Format::Json => writeln!(output, "{}", serde_json::to_string(stats)?),
+        Format::Csv => {
+            let deaths = Personality::ALL
+                .map(|p| stats.deaths_by_ghost.get(&p).copied().unwrap_or_default());
+            writeln!(
+                output,
+                "games,mean_score,mean_levels_cleared,deaths_by_blinky,deaths_by_pinky,\
+                deaths_by_inky,deaths_by_clyde,mean_frightened_seconds,\
+                mean_dots_remaining_at_death"
+            )?;
+            writeln!(
+                output,
+                "{},{:.1},{:.2},{},{},{},{},{:.1},{:.1}",
+                stats.games,
+                stats.mean_score,
+                stats.mean_levels_cleared,
+                deaths[0],
+                deaths[1],
+                deaths[2],
+                deaths[3],
+                stats.mean_frightened_seconds,
+                stats.mean_dots_remaining_at_death,
+            )
+        }
+    }
+}
@@ -0,0 +1,577 @@
+//! Copies of everything that changes while a game is played, so it can be put back to exactly how
+//! it was on an earlier frame, e.g. when [`netplay`](crate::netplay) guessed the other player's
+//! input wrong.
+//!
+//! Actors are matched up by which player or which ghost in the roster they are, rather than by
+//! entity, so a snapshot can still be restored after the level has been rebuilt.
+//!
+//! Snapshots can also be saved to a JSON file and loaded back, e.g. with the quick-save and
+//! quick-load keys in debug mode.
+
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::actor::ghost::{
+    Frightened, Ghost, GhostSpawner, Heading, InHouse, Respawning, RosterIndex, Target,
+};
+use crate::actor::mode::{FrightenedMode, FrightenedTimer, Mode, ModeTimer};
+use crate::actor::movement::{Dir, NextDir};
+use crate::actor::player::{InputBuffer, Lives, PlayerNumber};
+use crate::food::Food;
+use crate::grid::{Arrived, GridLocation, MovingTo, Speed};
+use crate::layout::{ActorKind, Layout};
+use crate::level::{self, CurrentLevel, LevelRestarted};
+use crate::maze::{CurrentMaze, Maze};
+use crate::players::Players;
+use crate::random::GameRng;
+use crate::score::{HighScore, PlayerScores, Score};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    actors: Vec<ActorSnapshot>,
+    /// Where the dots and energizers that haven't been eaten yet are
+    food: Vec<GridLocation>,
+    score: Score,
+    player_scores: PlayerScores,
+    high_score: HighScore,
+    lives: Lives,
+    players: Players,
+    level: CurrentLevel,
+    level_restarted: LevelRestarted,
+    /// Only the tiles, the portals are the maze's
+    #[serde(with = "as_text")]
+    layout: Layout,
+    #[serde(with = "as_text")]
+    maze: Maze,
+    mode: Mode,
+    frightened_mode: FrightenedMode,
+    mode_timer: ModeTimer,
+    frightened_timer: FrightenedTimer,
+    /// The random number generator can't be saved, so saved games are reseeded instead
+    #[serde(skip)]
+    rng: GameRng,
+}
+
+/// A snapshot as it's saved to a file
+#[derive(Serialize, Deserialize)]
+struct SavedGame {
+    /// What the random number generator was reseeded with when the game was saved
+    seed: u64,
+    #[serde(flatten)]
+    snapshot: Snapshot,
+}
+
+/// Which actor is which, in a way that survives the level being rebuilt
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum ActorId {
+    PacMan(usize),
+    /// Where the ghost is in the roster, since several can start on the same tile
+    Ghost(usize),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct ActorSnapshot {
+    id: ActorId,
+    kind: ActorKind,
+    translation: [f32; 3],
+    location: GridLocation,
+    arrived: bool,
+    dir: Dir,
+    next_dir: Option<Dir>,
+    moving_to: Option<[f32; 3]>,
+    speed: f32,
+    input: Option<InputBuffer>,
+    target: Option<Target>,
+    heading: Option<Heading>,
+    /// How many points the ghost is worth, while it's frightened
+    frightened: Option<u32>,
+    respawning: bool,
+    in_house: bool,
+}
+
+/// Something that went wrong saving, loading or restoring a snapshot
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    /// The actor isn't in the level any more, e.g. because it's a different maze
+    MissingActor(String),
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for SnapshotError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Json(err)
+    }
+}
+
+impl std::fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::Json(err) => write!(f, "invalid snapshot: {err}"),
+            Self::MissingActor(actor) => write!(f, "{actor} is missing from the level"),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl Snapshot {
+    /// Copies the game as it is between frames
+    pub fn take(world: &mut World) -> Self {
+        let actors = actors(world)
+            .into_iter()
+            .map(|(id, entity)| {
+                let entity = world.entity(entity);
+                ActorSnapshot {
+                    id,
+                    kind: *entity.get::<ActorKind>().unwrap(),
+                    translation: entity.get::<Transform>().unwrap().translation.to_array(),
+                    location: *entity.get::<GridLocation>().unwrap(),
+                    arrived: entity.contains::<Arrived>(),
+                    dir: *entity.get::<Dir>().unwrap(),
+                    next_dir: **entity.get::<NextDir>().unwrap(),
+                    moving_to: entity
+                        .get::<MovingTo>()
+                        .map(|moving_to| moving_to.to_array()),
+                    speed: **entity.get::<Speed>().unwrap(),
+                    input: entity.get::<InputBuffer>().cloned(),
+                    target: entity.get::<Target>().copied(),
+                    heading: entity.get::<Heading>().copied(),
+                    frightened: match entity.contains::<Frightened>() {
+                        true => entity.get::<Food>().map(|food| food.points),
+                        false => None,
+                    },
+                    respawning: entity.contains::<Respawning>(),
+                    in_house: entity.contains::<InHouse>(),
+                }
+            })
+            .collect();
+
+        let mut food: Vec<GridLocation> = world
+            .query_filtered::<&GridLocation, (With<Food>, Without<Ghost>)>()
+            .iter(world)
+            .copied()
+            .collect();
+        food.sort_by_key(|loc| (loc.x, loc.y));
+
+        Self {
+            actors,
+            food,
+            score: world.resource::<Score>().clone(),
+            player_scores: world.resource::<PlayerScores>().clone(),
+            high_score: world.resource::<HighScore>().clone(),
+            lives: world.resource::<Lives>().clone(),
+            players: world.resource::<Players>().clone(),
+            level: world.resource::<CurrentLevel>().clone(),
+            level_restarted: *world.resource::<LevelRestarted>(),
+            layout: world.resource::<Layout>().clone(),
+            maze: world.resource::<CurrentMaze>().0.clone(),
+            mode: *world.resource::<Mode>(),
+            frightened_mode: *world.resource::<FrightenedMode>(),
+            mode_timer: world.resource::<ModeTimer>().clone(),
+            frightened_timer: world.resource::<FrightenedTimer>().clone(),
+            rng: world.resource::<GameRng>().clone(),
+        }
+    }
+
+    /// Puts the game back how it was when the snapshot was taken.
+    ///
+    /// Nothing is marked as changed that wasn't already, so the next frame plays out the same as
+    /// it did the first time.
+    pub fn restore(&self, world: &mut World) -> Result<(), SnapshotError> {
+        let other_level = **world.resource::<CurrentLevel>() != *self.level;
+        self.put_back(world, other_level)
+    }
+
+    /// Saves the game to a file, as it is between frames.
+    ///
+    /// The random number generator is reseeded, so the game carries on the same way after it's
+    /// loaded back.
+    pub fn save(world: &mut World, path: &Path) -> Result<(), SnapshotError> {
+        let seed = world.resource_mut::<GameRng>().gen();
+        *world.resource_mut::<GameRng>() = GameRng::seeded(seed);
+
+        let saved = SavedGame {
+            seed,
+            snapshot: Self::take(world),
+        };
+        serde_json::to_writer(BufWriter::new(File::create(path)?), &saved)?;
+        Ok(())
+    }
+
+    /// Loads a game saved with [`Snapshot::save`], rebuilding the level if it's a different maze
+    pub fn load(world: &mut World, path: &Path) -> Result<(), SnapshotError> {
+        let SavedGame { seed, mut snapshot } =
+            serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        snapshot.rng = GameRng::seeded(seed);
+        snapshot.layout.portals = snapshot.maze.layout.portals.clone();
+
+        let other_level = **world.resource::<CurrentLevel>() != *snapshot.level
+            || world.resource::<CurrentMaze>().0.to_string() != snapshot.maze.to_string();
+        snapshot.put_back(world, other_level)?;
+
+        // Unlike a rollback, the screen has to catch up with everything that's different
+        world.resource_mut::<Score>().set_changed();
+        world.resource_mut::<HighScore>().set_changed();
+        world.resource_mut::<Lives>().set_changed();
+        world.resource_mut::<Players>().set_changed();
+        for mut dir in world.query::<&mut Dir>().iter_mut(world) {
+            dir.set_changed();
+        }
+        Ok(())
+    }
+
+    fn put_back(&self, world: &mut World, rebuild_level: bool) -> Result<(), SnapshotError> {
+        restore_resource(world, self.score.clone());
+        restore_resource(world, self.player_scores.clone());
+        restore_resource(world, self.high_score.clone());
+        restore_resource(world, self.lives.clone());
+        restore_resource(world, self.players.clone());
+        restore_resource(world, self.level.clone());
+        restore_resource(world, self.level_restarted);
+        restore_resource(world, self.mode);
+        restore_resource(world, self.frightened_mode);
+        restore_resource(world, self.mode_timer.clone());
+        restore_resource(world, self.frightened_timer.clone());
+        restore_resource(world, self.rng.clone());
+
+        if *self.level_restarted {
+            world.resource_mut::<CurrentLevel>().set_changed();
+        }
+
+        if rebuild_level {
+            // Everything that depends on the maze has to notice it's different
+            world.insert_resource(self.layout.clone());
+            world.insert_resource(CurrentMaze(self.maze.clone()));
+            level::rebuild_level(world);
+        }
+
+        self.restore_actors(world)?;
+        self.restore_food(world);
+        Ok(())
+    }
+
+    /// A number that's the same for two snapshots of the same game on the same frame, to check
+    /// that two copies of a game haven't drifted apart
+    pub fn checksum(&self) -> u64 {
+        let mut hasher = Fnv1a::default();
+        write!(
+            hasher,
+            "{:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?}",
+            self.actors,
+            self.food,
+            self.score,
+            self.player_scores,
+            self.lives,
+            self.level,
+            self.mode,
+            self.frightened_mode,
+            self.mode_timer,
+            self.frightened_timer,
+        )
+        .expect("Hashing can't fail");
+        hasher.0
+    }
+
+    pub fn score(&self) -> u32 {
+        *self.score
+    }
+
+    fn restore_actors(&self, world: &mut World) -> Result<(), SnapshotError> {
+        let entities: HashMap<ActorId, Entity> = actors(world).into_iter().collect();
+
+        for actor in &self.actors {
+            let entity = *entities
+                .get(&actor.id)
+                .ok_or_else(|| SnapshotError::MissingActor(format!("{:?}", actor.id)))?;
+
+            restore_component(world, entity, Some(actor.kind));
+            restore_component(world, entity, Some(actor.location));
+            restore_component(world, entity, Some(actor.dir));
+            restore_component(world, entity, actor.arrived.then_some(Arrived));
+            restore_component(
+                world,
+                entity,
+                actor
+                    .moving_to
+                    .map(|moving_to| MovingTo(Vec3::from_array(moving_to))),
+            );
+            restore_component(world, entity, Some(Speed(actor.speed)));
+            restore_component(world, entity, actor.input.clone());
+            restore_component(world, entity, actor.target);
+            restore_component(world, entity, actor.heading);
+            restore_component(world, entity, actor.respawning.then_some(Respawning));
+            restore_component(world, entity, actor.in_house.then_some(InHouse));
+
+            let mut entity_mut = world.entity_mut(entity);
+            if let Some(mut transform) = entity_mut.get_mut::<Transform>() {
+                transform.bypass_change_detection().translation =
+                    Vec3::from_array(actor.translation);
+            }
+            if let Some(mut next_dir) = entity_mut.get_mut::<NextDir>() {
+                **next_dir.bypass_change_detection() = actor.next_dir;
+            }
+
+            let personality = match entity_mut.get::<Ghost>() {
+                Some(ghost) => ghost.personality,
+                None => continue,
+            };
+            let frightened = actor.frightened.map(|points| Food { points });
+            restore_component(world, entity, actor.frightened.map(|_| Frightened));
+            restore_component(world, entity, frightened);
+
+            let look = world.resource::<GhostSpawner>().look(
+                personality,
+                actor.frightened.is_some(),
+                actor.respawning,
+            );
+            world.entity_mut(entity).insert(look);
+        }
+
+        Ok(())
+    }
+
+    fn restore_food(&self, world: &mut World) {
+        let mut current: HashMap<GridLocation, Entity> = world
+            .query_filtered::<(Entity, &GridLocation), (With<Food>, Without<Ghost>)>()
+            .iter(world)
+            .map(|(entity, location)| (*location, entity))
+            .collect();
+
+        let missing: Vec<GridLocation> = self
+            .food
+            .iter()
+            .filter(|location| current.remove(location).is_none())
+            .copied()
+            .collect();
+
+        // Whatever's left has been eaten since
+        for entity in current.into_values() {
+            world.entity_mut(entity).despawn_recursive();
+        }
+        level::respawn_food(world, &missing);
+    }
+}
+
+/// The FNV-1a hash, which unlike the standard library's hasher is the same in every build, so
+/// checksums can be compared between machines
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Write for Fnv1a {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+        Ok(())
+    }
+}
+
+/// Every Pac-Man and ghost in the level, in the same order every time
+fn actors(world: &mut World) -> Vec<(ActorId, Entity)> {
+    let mut actors: Vec<(ActorId, Entity)> = world
+        .query::<(Entity, Option<&PlayerNumber>, Option<&RosterIndex>)>()
+        .iter(world)
+        .filter_map(|(entity, player, ghost)| {
+            let id = match (player, ghost) {
+                (Some(player), _) => ActorId::PacMan(**player),
+                (None, Some(ghost)) => ActorId::Ghost(**ghost),
+                (None, None) => return None,
+            };
+            Some((id, entity))
+        })
+        .collect();
+
+    actors.sort_by_key(|(id, _)| *id);
+    actors
+}
+
+fn restore_resource<R: Resource>(world: &mut World, value: R) {
+    *world.resource_mut::<R>().bypass_change_detection() = value;
+}
+
+/// Sets a component without marking it as changed, or adds or removes it to match
+fn restore_component<T: Component>(world: &mut World, entity: Entity, value: Option<T>) {
+    let mut entity = world.entity_mut(entity);
+
+    match value {
+        Some(value) => {
+            if let Some(mut component) = entity.get_mut::<T>() {
+                *component.bypass_change_detection() = value;
+                return;
+            }
+            entity.insert(value);
+        }
+        None => {
+            entity.remove::<T>();
+        }
+    }
+}
+
+/// Saves a field with its text format, for things like mazes that already have one
+pub(crate) mod as_text {
+    use std::fmt::Display;
+    use std::str::FromStr;
+
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<T: Display, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+/// Saves a [`Timer`], which Bevy can't do without reflection
+pub(crate) mod saved_timer {
+    use std::time::Duration;
+
+    use bevy::prelude::*;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct SavedTimer {
+        duration: Duration,
+        elapsed: Duration,
+        repeating: bool,
+        paused: bool,
+        /// How many times it finished on the last tick
+        times_finished: u32,
+    }
+
+    pub fn serialize<S: Serializer>(timer: &Timer, serializer: S) -> Result<S::Ok, S::Error> {
+        SavedTimer {
+            duration: timer.duration(),
+            elapsed: timer.elapsed(),
+            repeating: timer.mode() == TimerMode::Repeating,
+            paused: timer.paused(),
+            times_finished: timer.times_finished_this_tick(),
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Timer, D::Error> {
+        let saved = SavedTimer::deserialize(deserializer)?;
+        let mode = match saved.repeating {
+            true => TimerMode::Repeating,
+            false => TimerMode::Once,
+        };
+
+        // Winds it back to before the last tick finished it, and lets it finish again
+        let mut timer = Timer::new(saved.duration, mode);
+        match mode {
+            TimerMode::Repeating => {
+                timer.set_elapsed(saved.elapsed + saved.duration * saved.times_finished)
+            }
+            TimerMode::Once => timer.set_elapsed(saved.elapsed),
+        }
+        timer.tick(Duration::ZERO);
+        if saved.times_finished == 0 {
+            // A timer that finished before then doesn't finish again
+            timer.tick(Duration::ZERO);
+        }
+        if saved.paused {
+            timer.pause();
+        }
+        Ok(timer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    struct Saved(#[serde(with = "saved_timer")] Timer);
+
+    fn save_and_load(timer: &Timer) -> Timer {
+        let json = serde_json::to_string(&Saved(timer.clone())).unwrap();
+        serde_json::from_str::<Saved>(&json).unwrap().0
+    }
+
+    fn assert_same(loaded: &Timer, timer: &Timer) {
+        assert_eq!(loaded.elapsed(), timer.elapsed());
+        assert_eq!(loaded.finished(), timer.finished());
+        assert_eq!(loaded.just_finished(), timer.just_finished());
+        assert_eq!(loaded.times_finished_this_tick(), timer.times_finished_this_tick());
+        assert_eq!(loaded.paused(), timer.paused());
+    }
+
+    #[test]
+    fn keeps_a_timer_that_finished_on_the_last_tick() {
+        let mut timer = Timer::from_seconds(1.0, TimerMode::Once);
+        timer.tick(Duration::from_millis(1500));
+
+        assert_same(&save_and_load(&timer), &timer);
+    }
+
+    #[test]
+    fn keeps_a_timer_that_finished_before_the_last_tick() {
+        let mut timer = Timer::from_seconds(1.0, TimerMode::Once);
+        timer.tick(Duration::from_millis(1500));
+        timer.tick(Duration::from_millis(100));
+
+        assert_same(&save_and_load(&timer), &timer);
+    }
+
+    #[test]
+    fn keeps_how_many_times_a_repeating_timer_finished() {
+        let mut timer = Timer::from_seconds(1.0, TimerMode::Repeating);
+        timer.tick(Duration::from_millis(2500));
+
+        let mut loaded = save_and_load(&timer);
+        assert_same(&loaded, &timer);
+
+        timer.tick(Duration::from_millis(600));
+        loaded.tick(Duration::from_millis(600));
+        assert_same(&loaded, &timer);
+    }
+
+    #[test]
+    fn keeps_a_paused_timer_paused() {
+        let mut timer = Timer::from_seconds(6.0, TimerMode::Once);
+        timer.tick(Duration::from_secs(2));
+        timer.pause();
+
+        let mut loaded = save_and_load(&timer);
+        assert_same(&loaded, &timer);
+
+        loaded.tick(Duration::from_secs(10));
+        assert_eq!(loaded.elapsed(), Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,222 @@
+//! Streams the state of the game to anything that connects over TCP, one line of JSON per frame,
+//! so overlays, dashboards and bots can watch a game without linking the crate.
+//!
+//! It's off unless `TELEMETRY` is set to an address to listen on:
+//!
+//! ```text
+//! TELEMETRY=127.0.0.1:7878 pac-man
+//! nc 127.0.0.1 7878
+//! < {"frame":1,"level":1,"mode":"scatter","frightened":false,"score":0,"lives":3,"actors":[...]}
+//! ```
+//!
+//! Frames are sent from another thread, so a slow watcher never holds up the game. Frames that
+//! can't be sent in time are skipped, and watchers that stop reading are disconnected.
+
+use std::io::{self, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::actor::ghost::{Frightened, Ghost, Personality, Respawning, Target};
+use crate::actor::mode::{FrightenedMode, Mode};
+use crate::actor::movement::Dir;
+use crate::actor::player::{Lives, PlayerNumber};
+use crate::grid::GridLocation;
+use crate::layout::ActorKind;
+use crate::level::CurrentLevel;
+use crate::score::Score;
+
+/// How many frames can be waiting to be sent before new ones are skipped
+const BACKLOG: usize = 120;
+
+/// How long a watcher has to take a frame before it's disconnected
+const WRITE_TIMEOUT: Duration = Duration::from_millis(250);
+
+pub struct TelemetryPlugin;
+
+impl Plugin for TelemetryPlugin {
+    fn build(&self, app: &mut App) {
+        let address = match get_telemetry_address() {
+            Some(address) => address,
+            None => return,
+        };
+
+        match Telemetry::listen(address) {
+            Ok(telemetry) => {
+                info!("Streaming telemetry on {address}");
+                app.insert_resource(telemetry)
+                    .add_system_to_stage(CoreStage::Last, publish_frame);
+            }
+            Err(err) => error!("Couldn't stream telemetry on {address}: {err}"),
+        }
+    }
+}
+
+/// Where frames go to be sent to everyone watching
+#[derive(Resource)]
+pub struct Telemetry {
+    frames: SyncSender<String>,
+}
+
+impl Telemetry {
+    /// Starts accepting watchers on an address
+    pub fn listen(address: SocketAddr) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let watchers: Arc<Mutex<Vec<TcpStream>>> = Arc::default();
+        let (frames, received) = mpsc::sync_channel::<String>(BACKLOG);
+
+        let accepted = watchers.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if stream.set_write_timeout(Some(WRITE_TIMEOUT)).is_ok() {
+                    let _ = stream.set_nodelay(true);
+                    accepted.lock().unwrap().push(stream);
+                }
+            }
+        });
+
+        thread::spawn(move || {
+            for frame in received {
+                watchers
+                    .lock()
+                    .unwrap()
+                    .retain_mut(|watcher| writeln!(watcher, "{frame}").is_ok());
+            }
+        });
+
+        Ok(Self { frames })
+    }
+}
+
+/// Everything going on in one frame of the game
+#[derive(Serialize, Clone, Debug)]
+pub struct Frame {
+    /// Counting from when the game started
+    pub frame: u64,
+    pub level: usize,
+    pub mode: Mode,
+    pub frightened: bool,
+    pub score: u32,
+    pub lives: usize,
+    pub actors: Vec<ActorFrame>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ActorFrame {
+    pub name: String,
+    pub role: ActorRole,
+    /// Which Pac-Man this is, for Pac-Men
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub player: Option<usize>,
+    /// For ghosts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub personality: Option<Personality>,
+    pub location: GridLocation,
+    pub dir: Dir,
+    /// Where a ghost is heading for, if anywhere. Frightened ghosts wander without one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<GridLocation>,
+    pub frightened: bool,
+    /// Eaten, and on the way home as a pair of eyes
+    pub respawning: bool,
+}
+
+#[derive(Serialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum ActorRole {
+    PacMan,
+    Ghost,
+}
+
+type Watched<'a> = (
+    &'a Name,
+    &'a ActorKind,
+    &'a GridLocation,
+    &'a Dir,
+    Option<&'a PlayerNumber>,
+    Option<&'a Ghost>,
+    Option<&'a Target>,
+    Option<&'a Frightened>,
+    Option<&'a Respawning>,
+);
+
+#[allow(clippy::too_many_arguments)]
+fn publish_frame(
+    telemetry: Res<Telemetry>,
+    mut frame: Local<u64>,
+    level: Res<CurrentLevel>,
+    mode: Res<Mode>,
+    frightened: Res<FrightenedMode>,
+    score: Res<Score>,
+    lives: Res<Lives>,
+    actors: Query<Watched>,
+) {
+    *frame += 1;
+
+    let mut actors: Vec<ActorFrame> = actors
+        .iter()
+        .map(
+            |(name, kind, location, dir, player, ghost, target, frightened, respawning)| {
+                ActorFrame {
+                    name: name.to_string(),
+                    role: match kind {
+                        ActorKind::PacMan => ActorRole::PacMan,
+                        _ => ActorRole::Ghost,
+                    },
+                    player: player.map(|player| **player),
+                    personality: ghost.map(|ghost| ghost.personality),
+                    location: *location,
+                    dir: *dir,
+                    // The target can outlive being frightened by a frame
+                    target: target
+                        .filter(|_| frightened.is_none())
+                        .map(|target| **target),
+                    frightened: frightened.is_some(),
+                    respawning: respawning.is_some(),
+                }
+            },
+        )
+        .collect();
+    // Pac-Men first, then in a steady order so watchers can tell the ghosts apart
+    actors.sort_by_key(|actor| {
+        (
+            actor.role == ActorRole::Ghost,
+            actor.player,
+            actor.name.clone(),
+        )
+    });
+
+    let frame = Frame {
+        frame: *frame,
+        level: **level,
+        mode: *mode,
+        frightened: *frightened == FrightenedMode::Enabled,
+        score: **score,
+        lives: **lives,
+        actors,
+    };
+
+    match serde_json::to_string(&frame) {
+        // Skipped if the watchers are too far behind
+        Ok(line) => {
+            let _ = telemetry.frames.try_send(line);
+        }
+        Err(err) => error!("Couldn't serialize telemetry: {err}"),
+    }
+}
+
+fn get_telemetry_address() -> Option<SocketAddr> {
+    let address = std::env::var("TELEMETRY").ok()?;
+    match address.parse() {
+        Ok(address) => Some(address),
+        Err(err) => {
+            warn!("Not streaming telemetry to {address:?}, expected an IP and port: {err}");
+            None
+        }
+    }
+}
@@ -1,11 +1,12 @@
 use std::cmp::Ordering;
+use std::time::Duration;
 
 use crate::actor::player::{Lives, UpdateLives};
 use crate::grid::{GridBundle, GridLocation, Layer};
-use crate::level::GRID;
-use crate::score::{HighScore, Score, UpdateHighScore, UpdateScore};
+use crate::level::{CurrentLevel, StartLevel, GRID, HEIGHT_TILES};
+use crate::players::{PacMen, Players};
+use crate::score::{HighScore, PlayerScores, Score, UpdateHighScore, UpdateScore};
 use crate::text::{Align, SetTextSprites, TextBundle, TextPlugin, TextSprites};
-use crate::HEIGHT_TILES;
 use bevy::math::Rect;
 use bevy::prelude::*;
 use bevy::sprite::Anchor;
@@ -24,6 +25,12 @@ impl Plugin for UIPlugin {
                         update_high_score_display
                             .ambiguous_with(update_score_display)
                             .after(UpdateHighScore),
+                    )
+                    .with_system(
+                        update_ready_display
+                            .ambiguous_with(update_score_display)
+                            .ambiguous_with(update_high_score_display)
+                            .after(StartLevel),
                     ),
             )
             .add_system(update_lives_display.after(UpdateLives))
@@ -31,8 +38,9 @@ impl Plugin for UIPlugin {
     }
 }
 
+/// The score of one of the [`Players`] or [`PacMen`], starting from 0
 #[derive(Component)]
-struct ScoreDisplay;
+struct ScoreDisplay(usize);
 
 #[derive(Component)]
 struct HighScoreDisplay;
@@ -40,6 +48,20 @@ struct HighScoreDisplay;
 #[derive(Component)]
 struct LivesDisplay;
 
+/// The "PLAYER ONE" or "PLAYER TWO" above the ghost house, which is only shown with more than
+/// one player
+#[derive(Component)]
+struct PlayerDisplay;
+
+/// The "READY!" under the ghost house
+#[derive(Component)]
+struct ReadyDisplay;
+
+/// How long the ready text stays up at the start of each turn
+const READY_TIME: Duration = Duration::from_secs(2);
+
+const PLAYER_NAMES: [&str; 4] = ["PLAYER ONE", "PLAYER TWO", "PLAYER THREE", "PLAYER FOUR"];
+
 #[derive(Resource, Deref, DerefMut)]
 struct UIAssets(Handle<TextureAtlas>);
 
@@ -61,7 +83,14 @@ impl FromWorld for UIAssets {
     }
 }
 
-fn setup_ui(mut commands: Commands) {
+fn setup_ui(mut commands: Commands, players: Res<Players>, pac_men: Res<PacMen>) {
+    let two_up = players.count() > 1 || **pac_men > 1;
+    let static_text = if two_up {
+        "1UP   HIGH SCORE   2UP"
+    } else {
+        "1UP   HIGH SCORE"
+    };
+
     commands
         .spawn((SpatialBundle::default(), Name::new("UI")))
         .with_children(|builder| {
@@ -69,7 +98,7 @@ fn setup_ui(mut commands: Commands) {
                 .spawn((
                     TextBundle {
                         text: TextSprites {
-                            string: "1UP   HIGH SCORE".to_string(),
+                            string: static_text.to_string(),
                             ..default()
                         },
                         ..default()
@@ -103,9 +132,32 @@ fn setup_ui(mut commands: Commands) {
                         Layer::UI,
                     ),
                     Name::new("Score"),
-                    ScoreDisplay,
+                    ScoreDisplay(0),
                 ));
 
+            if two_up {
+                builder
+                    .spawn(TextBundle {
+                        text: TextSprites {
+                            align: Align::Right,
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .insert((
+                        GridBundle::new(
+                            GRID,
+                            GridLocation {
+                                x: 25,
+                                y: HEIGHT_TILES as isize - 2,
+                            },
+                            Layer::UI,
+                        ),
+                        Name::new("2UP Score"),
+                        ScoreDisplay(1),
+                    ));
+            }
+
             builder
                 .spawn(TextBundle {
                     text: TextSprites {
@@ -127,6 +179,18 @@ fn setup_ui(mut commands: Commands) {
                     HighScoreDisplay,
                 ));
 
+            builder.spawn(TextBundle::default()).insert((
+                GridBundle::new(GRID, GridLocation { x: 9, y: 21 }, Layer::UI),
+                Name::new("Player"),
+                PlayerDisplay,
+            ));
+
+            builder.spawn(TextBundle::default()).insert((
+                GridBundle::new(GRID, GridLocation { x: 11, y: 15 }, Layer::UI),
+                Name::new("Ready"),
+                ReadyDisplay,
+            ));
+
             builder.spawn((
                 GridBundle::new(GRID, GridLocation { x: 2, y: 0 }, Layer::UI),
                 VisibilityBundle::default(),
@@ -136,12 +200,23 @@ fn setup_ui(mut commands: Commands) {
         });
 }
 
-fn update_score_display(score: Res<Score>, mut query: Query<&mut TextSprites, With<ScoreDisplay>>) {
-    if !score.is_changed() {
+fn update_score_display(
+    score: Res<Score>,
+    player_scores: Res<PlayerScores>,
+    players: Res<Players>,
+    pac_men: Res<PacMen>,
+    mut query: Query<(&mut TextSprites, &ScoreDisplay)>,
+) {
+    if !score.is_changed() && !players.is_changed() {
         return;
     }
 
-    for mut text in &mut query {
+    for (mut text, display) in &mut query {
+        let score = if **pac_men > 1 {
+            player_scores.get(display.0)
+        } else {
+            players.score(display.0, &score)
+        };
         text.string = score.to_string();
     }
 }
@@ -207,3 +282,35 @@ fn update_lives_display(
         }
     }
 }
+
+/// Shows whose turn it is for a couple of seconds whenever a level starts or restarts, like after
+/// each death
+fn update_ready_display(
+    time: Res<Time>,
+    level: Res<CurrentLevel>,
+    players: Res<Players>,
+    mut timer: Local<Timer>,
+    mut player_text: Query<&mut TextSprites, (With<PlayerDisplay>, Without<ReadyDisplay>)>,
+    mut ready_text: Query<&mut TextSprites, With<ReadyDisplay>>,
+) {
+    let (player, ready) = if level.is_changed() {
+        *timer = Timer::new(READY_TIME, TimerMode::Once);
+        let player = match players.count() {
+            1 => "",
+            _ => PLAYER_NAMES.get(players.current()).copied().unwrap_or(""),
+        };
+        (player, "READY!")
+    } else if timer.tick(time.delta()).just_finished() {
+        ("", "")
+    } else {
+        return;
+    };
+
+    for mut text in &mut player_text {
+        text.string = player.to_string();
+    }
+
+    for mut text in &mut ready_text {
+        text.string = ready.to_string();
+    }
+}